@@ -6,4 +6,6 @@ cp -u target/wasm32-unknown-unknown/release/metapool.wasm res/
 cp -u target/wasm32-unknown-unknown/release/meta_token.wasm res/
 #cp -u target/wasm32-unknown-unknown/release/staking_pool.wasm res/
 cp -u target/wasm32-unknown-unknown/release/get_epoch_contract.wasm res/
+cp -u target/wasm32-unknown-unknown/release/mock_staking_pool.wasm res/
+cp -u target/wasm32-unknown-unknown/release/mock_wnear.wasm res/w_near.wasm
 
@@ -26,9 +26,10 @@ pub struct Account {
     pub stake_shares: u128, //st_near this account owns
 
     /// Incremented when the user asks for Delayed-Unstaking. The amount of unstaked near in the pools
+    /// unstaked == sum(claims.amount), see MetaPool.unstake_claims
     pub unstaked: u128,
 
-    /// The epoch height when the unstaked will be available
+    /// The epoch height when *all* the unstaked will be available (the latest claim unlock epoch)
     /// The funds will be locked for -AT LEAST- NUM_EPOCHS_TO_UNLOCK epochs
     pub unstaked_requested_unlock_epoch: EpochHeight,
 
@@ -182,10 +183,12 @@ impl Account {
 
     /// user method
     /// completes unstake action by moving from acc.unstaked & main.reserve_for_unstaked_claims -> acc.available & main.total_available
+    /// only matured claims can be used, they're consumed from `claims`
     pub fn in_memory_try_finish_unstaking(
         &mut self,
         account_id: &str,
         amount: u128,
+        claims: &mut Vec<UnstakeClaim>,
         main: &mut MetaPool,
     ) -> u128 {
         assert!(
@@ -195,12 +198,14 @@ impl Account {
         );
 
         let epoch = env::epoch_height();
-        assert!( epoch >= self.unstaked_requested_unlock_epoch,
+        assert!( amount <= matured_claims_amount(claims, epoch),
             "The unstaked balance is not yet available due to unstaking delay. You need to wait at least {} epochs"
-            , self.unstaked_requested_unlock_epoch - epoch);
+            , epochs_to_next_unlock(claims, epoch));
 
+        // consume the matured claims
+        consume_matured_claims(claims, amount, epoch);
         // in the account, moves from unstaked to available
-        self.unstaked -= amount; //Zeroes, claimed
+        self.unstaked -= amount;
         self.available += amount;
         //check the heart beat has really moved the funds
         assert!(
@@ -214,13 +219,7 @@ impl Account {
         main.total_unstake_claims -= amount;
         main.total_available += amount;
 
-        event!(
-            r#"{{"event":"D-WITHD","account_id":"{}","amount":"{}"}}"#,
-            account_id,
-            amount
-        );
-
-        log!("{} unstaked moved to available", amount);
+        log!("@{} {} unstaked moved to available", account_id, amount);
 
         return amount;
     }
@@ -254,3 +253,102 @@ impl Account {
         return to_withdraw;
     }
 }
+
+//-----------------------
+// Delayed-unstake claims
+//-----------------------
+/// A pending delayed-unstake claim. Every delayed-unstake creates a claim with its own unlock epoch,
+/// so unstaking again does not push forward the unlock epoch of the previous claims
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq, Clone)]
+pub struct UnstakeClaim {
+    pub amount: u128,
+    /// The epoch height when this claim can be withdrawn
+    pub unlock_epoch: EpochHeight,
+}
+
+/// adds a new claim, merging it with an existing claim if both unlock on the same epoch
+pub fn add_unstake_claim(claims: &mut Vec<UnstakeClaim>, amount: u128, unlock_epoch: EpochHeight) {
+    for claim in claims.iter_mut() {
+        if claim.unlock_epoch == unlock_epoch {
+            claim.amount += amount;
+            return;
+        }
+    }
+    assert!(
+        claims.len() < MAX_UNSTAKE_CLAIMS_PER_ACCOUNT,
+        "Too many pending unstake claims ({}). Withdraw the matured ones first",
+        claims.len()
+    );
+    claims.push(UnstakeClaim {
+        amount,
+        unlock_epoch,
+    });
+}
+
+/// sum of the claims that can be withdrawn at `epoch`
+pub fn matured_claims_amount(claims: &Vec<UnstakeClaim>, epoch: EpochHeight) -> u128 {
+    claims
+        .iter()
+        .filter(|claim| claim.unlock_epoch <= epoch)
+        .map(|claim| claim.amount)
+        .sum()
+}
+
+/// how many epochs until the next claim unlocks (0 if there are no pending claims)
+pub fn epochs_to_next_unlock(claims: &Vec<UnstakeClaim>, epoch: EpochHeight) -> EpochHeight {
+    claims
+        .iter()
+        .filter(|claim| claim.unlock_epoch > epoch)
+        .map(|claim| claim.unlock_epoch - epoch)
+        .min()
+        .unwrap_or(0)
+}
+
+/// removes `amount` from the claims still in the waiting period, nearest unlock first
+/// returns the consumed parts
+pub fn take_immature_claims(
+    claims: &mut Vec<UnstakeClaim>,
+    amount: u128,
+    epoch: EpochHeight,
+) -> Vec<UnstakeClaim> {
+    let mut taken: Vec<UnstakeClaim> = Vec::new();
+    claims.sort_by_key(|claim| claim.unlock_epoch);
+    let mut to_consume = amount;
+    for claim in claims.iter_mut() {
+        if to_consume == 0 {
+            break;
+        }
+        if claim.unlock_epoch > epoch {
+            let part = std::cmp::min(claim.amount, to_consume);
+            claim.amount -= part;
+            to_consume -= part;
+            taken.push(UnstakeClaim {
+                amount: part,
+                unlock_epoch: claim.unlock_epoch,
+            });
+        }
+    }
+    assert!(
+        to_consume == 0,
+        "Not enough unstake claims in the waiting period for the requested amount"
+    );
+    claims.retain(|claim| claim.amount > 0);
+    return taken;
+}
+
+/// removes `amount` from the matured claims, dropping the claims fully consumed
+fn consume_matured_claims(claims: &mut Vec<UnstakeClaim>, amount: u128, epoch: EpochHeight) {
+    let mut to_consume = amount;
+    for claim in claims.iter_mut() {
+        if to_consume == 0 {
+            break;
+        }
+        if claim.unlock_epoch <= epoch {
+            let taken = std::cmp::min(claim.amount, to_consume);
+            claim.amount -= taken;
+            to_consume -= taken;
+        }
+    }
+    assert!(to_consume == 0, "inconsistency: not enough matured claims");
+    claims.retain(|claim| claim.amount > 0);
+}
@@ -0,0 +1,260 @@
+//! Automatic staking-pool weights, computed from the recorded performance (sp_performance)
+//! score = average annualized reward rate * uptime. Weights are proportional to the score,
+//! capped at MAX_SP_WEIGHT_BASIS_POINTS each. Pools with a manual override keep their weight,
+//! busy pools (an op in flight was sized with their weight) keep their current weight
+use crate::*;
+
+impl StakingPoolPerformance {
+    /// annualized reward rate over the recorded history, in basis points
+    pub fn avg_reward_rate_bp(&self) -> u32 {
+        let mut rewards: u128 = 0;
+        let mut staked_epochs: u128 = 0;
+        for record in self.history.iter() {
+            rewards += record.rewards;
+            staked_epochs = staked_epochs.saturating_add(record.staked.saturating_mul(record.epochs as u128));
+        }
+        if staked_epochs == 0 {
+            return 0;
+        }
+        return proportional(10_000 * EPOCHS_PER_YEAR as u128, rewards, staked_epochs) as u32;
+    }
+
+    /// % of the reports with rewards, in basis points
+    pub fn uptime_bp(&self) -> u32 {
+        if self.epochs_reported == 0 {
+            return 0;
+        }
+        return (self.epochs_reported - self.epochs_missed) * 10_000 / self.epochs_reported;
+    }
+
+    pub fn score(&self) -> u128 {
+        return self.avg_reward_rate_bp() as u128 * self.uptime_bp() as u128;
+    }
+}
+
+/// distributes `available` basis points proportionally to the scores, no item over MAX_SP_WEIGHT_BASIS_POINTS
+/// returns None if the scores can't absorb all the basis points
+pub fn compute_auto_weights(scores: &Vec<u128>, available: u16) -> Option<Vec<u16>> {
+    let mut weights: Vec<u16> = vec![0; scores.len()];
+    let mut open: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] > 0).collect();
+    let mut remaining = available;
+    // proportional, repeating while some item goes over the cap
+    while remaining > 0 && open.len() > 0 {
+        let score_sum: u128 = open.iter().map(|&i| scores[i]).sum();
+        let over_cap: Vec<usize> = open
+            .iter()
+            .cloned()
+            .filter(|&i| proportional(remaining as u128, scores[i], score_sum) >= MAX_SP_WEIGHT_BASIS_POINTS as u128)
+            .collect();
+        if over_cap.len() == 0 {
+            let mut assigned: u16 = 0;
+            for &i in open.iter() {
+                weights[i] = proportional(remaining as u128, scores[i], score_sum) as u16;
+                assigned += weights[i];
+            }
+            remaining -= assigned;
+            break;
+        }
+        for &i in over_cap.iter() {
+            let w = std::cmp::min(MAX_SP_WEIGHT_BASIS_POINTS, remaining);
+            weights[i] = w;
+            remaining -= w;
+        }
+        open.retain(|i| !over_cap.contains(i));
+    }
+    // rounding leftovers, one bp at a time, best scores first
+    let mut by_score: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] > 0).collect();
+    by_score.sort_by(|&a, &b| scores[b].cmp(&scores[a]));
+    while remaining > 0 {
+        let mut placed = false;
+        for &i in by_score.iter() {
+            if remaining > 0 && weights[i] < MAX_SP_WEIGHT_BASIS_POINTS {
+                weights[i] += 1;
+                remaining -= 1;
+                placed = true;
+            }
+        }
+        if !placed {
+            return None;
+        }
+    }
+    return Some(weights);
+}
+
+impl MetaPool {
+    pub(crate) fn internal_sp_weight_override(&self, account_id: &AccountId) -> Option<u16> {
+        return self
+            .sp_weight_overrides
+            .iter()
+            .find(|item| &item.0 == account_id)
+            .map(|item| item.1);
+    }
+
+    /// the weight a pool keeps when the others are recomputed: busy pools keep their current weight,
+    /// else the override if any. None if the pool takes part in the distribution
+    pub(crate) fn internal_sp_fixed_weight(&self, sp: &StakingPoolInfo) -> Option<u16> {
+        if sp.is_busy() {
+            return Some(sp.weight_basis_points);
+        }
+        return self.internal_sp_weight_override(&sp.account_id);
+    }
+
+    /// sets the weight of the pool at inx, spreading the difference on the other pools proportionally to their weights
+    /// (pools with an override and busy pools keep their weight), so sum(weights) stays 100%
+    /// returns false, with no changes, if the difference can't be spread
+    pub(crate) fn internal_set_sp_weight(&mut self, inx: usize, weight_basis_points: u16) -> bool {
+        let mut fixed_total: u32 = weight_basis_points as u32;
+        let mut scores: Vec<u128> = vec![0; self.staking_pools.len()];
+        for i in 0..self.staking_pools.len() {
+            let sp = &self.staking_pools[i];
+            if i == inx {
+                continue;
+            }
+            if self.internal_sp_fixed_weight(sp).is_some() {
+                fixed_total += sp.weight_basis_points as u32;
+            } else {
+                scores[i] = sp.weight_basis_points as u128;
+            }
+        }
+        if fixed_total > 10000 {
+            return false;
+        }
+        let weights = match compute_auto_weights(&scores, (10000 - fixed_total) as u16) {
+            Some(weights) => weights,
+            None => return false,
+        };
+        for i in 0..self.staking_pools.len() {
+            if i == inx {
+                self.staking_pools[i].weight_basis_points = weight_basis_points;
+            } else if scores[i] > 0 {
+                self.staking_pools[i].weight_basis_points = weights[i];
+            }
+        }
+        return true;
+    }
+
+    /// sum of the overrides of the pools in the list (overrides of removed pools are ignored)
+    pub(crate) fn internal_sp_weight_overrides_total(&self) -> u16 {
+        return self
+            .sp_weight_overrides
+            .iter()
+            .filter(|item| self.is_staking_pool_in_list(&item.0))
+            .map(|item| item.1)
+            .sum();
+    }
+
+    /// new weights for all the pools: overrides & busy pools current weight + computed from performance
+    pub(crate) fn internal_compute_auto_weights(&self) -> Vec<u16> {
+        let overrides_total = self.internal_sp_weight_overrides_total();
+        assert!(overrides_total <= 10000, "overrides sum more than 100%");
+
+        let fixed: Vec<Option<u16>> = self
+            .staking_pools
+            .iter()
+            .map(|sp| self.internal_sp_fixed_weight(sp))
+            .collect();
+        let fixed_total: u32 = fixed.iter().map(|bp| bp.unwrap_or(0) as u32).sum();
+        assert!(fixed_total <= 10000, "overrides & busy pools sum more than 100%");
+
+        let scores: Vec<u128> = self
+            .staking_pools
+            .iter()
+            .enumerate()
+            .map(|(inx, sp)| match fixed[inx] {
+                Some(_) => 0,
+                None => self.sp_performance.get(&sp.account_id).map_or(0, |perf| perf.score()),
+            })
+            .collect();
+        let mut weights = compute_auto_weights(&scores, (10000 - fixed_total) as u16)
+            .expect("not enough performing pools to distribute the weights");
+
+        for inx in 0..self.staking_pools.len() {
+            if let Some(bp) = fixed[inx] {
+                weights[inx] = bp;
+            }
+        }
+        let pools_with_weight = weights.iter().filter(|&&bp| bp > 0).count();
+        assert!(
+            pools_with_weight >= self.auto_weights_min_pools as usize,
+            "only {} pools would have weight, min is {}",
+            pools_with_weight,
+            self.auto_weights_min_pools
+        );
+        return weights;
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    /// public method, once per epoch when the automatic mode is enabled (operator & owner: always)
+    /// sets weight_basis_points for all the pools from their recorded performance, keeping the manual overrides.
+    /// Busy pools keep their weight
+    pub fn auto_rebalance_weights(&mut self) {
+        let caller = env::predecessor_account_id();
+        let is_operator_or_owner =
+            caller == self.owner_account_id || caller == self.operator_account_id;
+        if !is_operator_or_owner {
+            assert!(self.auto_weights_enabled, "automatic weights are not enabled");
+            assert!(
+                env::epoch_height() > self.auto_weights_last_epoch,
+                "weights already rebalanced this epoch"
+            );
+        }
+
+        let weights = self.internal_compute_auto_weights();
+        for inx in 0..self.staking_pools.len() {
+            self.staking_pools[inx].weight_basis_points = weights[inx];
+        }
+        self.auto_weights_last_epoch = env::epoch_height();
+
+        event!("auto_weights", {
+            "weights": weights.iter().map(|bp| bp.to_string()).collect::<Vec<String>>(),
+        });
+    }
+
+    /// what auto_rebalance_weights would set now, one item per pool
+    pub fn get_auto_weights_preview(&self) -> Vec<u16> {
+        return self.internal_compute_auto_weights();
+    }
+
+    pub fn get_auto_weights_config(&self) -> AutoWeightsConfigJSON {
+        return AutoWeightsConfigJSON {
+            enabled: self.auto_weights_enabled,
+            min_pools: self.auto_weights_min_pools,
+            last_epoch: self.auto_weights_last_epoch.into(),
+            overrides: self
+                .sp_weight_overrides
+                .iter()
+                .map(|item| StakingPoolArgItem {
+                    account_id: item.0.clone(),
+                    weight_basis_points: item.1,
+                })
+                .collect(),
+        };
+    }
+
+    /// owner method
+    pub fn set_auto_weights_config(&mut self, enabled: bool, min_pools: u16) {
+        self.assert_owner_calling();
+        self.auto_weights_enabled = enabled;
+        self.auto_weights_min_pools = min_pools;
+    }
+
+    /// operator/owner method
+    /// fixes the weight of a pool, auto_rebalance_weights will not change it. weight_basis_points=null removes the override
+    pub fn set_sp_weight_override(&mut self, account_id: AccountId, weight_basis_points: Option<u16>) {
+        self.assert_operator_or_owner();
+        self.sp_weight_overrides.retain(|item| item.0 != account_id);
+        if let Some(bp) = weight_basis_points {
+            assert!(bp <= MAX_SP_WEIGHT_BASIS_POINTS, "no staking pool can have 50% or more");
+            assert!(
+                self.staking_pools.iter().any(|sp| sp.account_id == account_id),
+                "{} is not in the staking pool list",
+                account_id
+            );
+            self.sp_weight_overrides.push((account_id, bp));
+            let overrides_total = self.internal_sp_weight_overrides_total();
+            assert!(overrides_total <= 10000, "overrides sum more than 100%");
+        }
+    }
+}
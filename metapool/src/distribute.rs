@@ -1,5 +1,5 @@
 use crate::*;
-use near_sdk::{log, near_bindgen, Promise};
+use near_sdk::{log, near_bindgen, Promise, PromiseOrValue, PromiseResult};
 
 #[near_bindgen]
 impl MetaPool {
@@ -52,6 +52,9 @@ impl MetaPool {
             log!("amount too low {}", total_amount_to_stake);
             return false;
         }
+        if self.max_parallel_pools > 1 {
+            return self.distribute_staking_parallel(total_amount_to_stake);
+        }
         // find pool
         let (sp_inx, amount_to_stake) =
             self.get_staking_pool_requiring_stake(total_amount_to_stake);
@@ -61,22 +64,63 @@ impl MetaPool {
             sp_inx,
             amount_to_stake
         );
-        // schedule stake or re-stake promises
-        self.direct_stake(sp_inx, amount_to_stake);
+        // schedule stake or re-stake promises, the keeper is paid by the callback
+        self.direct_stake(sp_inx, amount_to_stake, true, Some(env::predecessor_account_id()));
         return true; //did some staking (promises scheduled), call again
     }
 
+    // parallel mode (max_parallel_pools > 1): stake on up to max_parallel_pools pools at once.
+    // only the pools are locked, each callback reconciles its own pool
+    fn distribute_staking_parallel(&mut self, total_amount_to_stake: u128) -> bool {
+        let gas_per_pool =
+            gas::staking_pool::DEPOSIT_AND_STAKE + gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE;
+        let mut remaining = total_amount_to_stake;
+        let mut scheduled: u16 = 0;
+        while scheduled < self.max_parallel_pools
+            && remaining >= MIN_STAKE_AMOUNT
+            && env::prepaid_gas() - env::used_gas() >= gas_per_pool + gas::BASE_GAS
+        {
+            // busy pools are skipped, so each iteration selects another pool
+            let (sp_inx, amount_to_stake) = self.get_staking_pool_requiring_stake(remaining);
+            if amount_to_stake == 0 {
+                break;
+            }
+            remaining -= self.direct_stake(
+                sp_inx,
+                amount_to_stake,
+                false,
+                Some(env::predecessor_account_id()),
+            );
+            scheduled += 1;
+        }
+        log!("staking scheduled on {} pools", scheduled);
+        return scheduled > 0;
+    }
+
     // internal direct stake on a pool
     // prev fn continues here
     // schedules promises to stake or re-stake
-    fn direct_stake(&mut self, sp_inx:usize, mut amount_to_stake:u128) {
+    // lock_contract=false for parallel mode (only the pool is locked)
+    // keeper_id: the caller of the heartbeat fn, paid the keeper bounty if the stake succeeds
+    // returns the amount scheduled to stake
+    fn direct_stake(
+        &mut self,
+        sp_inx: usize,
+        mut amount_to_stake: u128,
+        lock_contract: bool,
+        keeper_id: Option<AccountId>,
+    ) -> u128 {
 
         if amount_to_stake > 0 {
             //most unbalanced pool found & available
 
-            self.contract_busy = true;
+            if lock_contract {
+                self.contract_busy = true;
+                self.contract_busy_since = env::block_index();
+            }
             let sp = &mut self.staking_pools[sp_inx];
-            sp.busy_lock = true;
+            sp.lock();
+            let lock_since = sp.busy_lock_since;
 
             //case 1. pool has unstaked amount (we could be at the unstaking delay waiting period)
             //NOTE: The amount to stake can't be so low as a few yoctos because the staking-pool
@@ -101,6 +145,9 @@ impl MetaPool {
                     sp_inx,
                     amount_to_stake,
                     false,
+                    lock_contract,
+                    lock_since,
+                    keeper_id.clone(),
                     &env::current_account_id(),
                     NO_DEPOSIT,
                     gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE,
@@ -127,6 +174,9 @@ impl MetaPool {
                     sp_inx,
                     amount_to_stake,
                     true,
+                    lock_contract,
+                    lock_since,
+                    keeper_id.clone(),
                     &env::current_account_id(),
                     NO_DEPOSIT,
                     gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE,
@@ -135,8 +185,17 @@ impl MetaPool {
         }
 
         //Here we did some staking (the promises are scheduled for exec after this fn completes)
+        if amount_to_stake > 0 {
+            let sp = &mut self.staking_pools[sp_inx];
+            sp.pending_op = PendingOp::Stake {
+                amount: amount_to_stake,
+                included_deposit: sp.unstaked < TEN_NEAR,
+            };
+            sp.pending_op_since = sp.busy_lock_since;
+        }
         self.total_actually_staked += amount_to_stake; //preventively consider the amount staked (undoes if async fails)
         self.epoch_stake_orders -= amount_to_stake; //preventively reduce stake orders
+        return amount_to_stake;
     }
 
     //prev fn continues here
@@ -147,17 +206,43 @@ impl MetaPool {
         sp_inx: usize,
         amount: u128,
         included_deposit: bool,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        keeper_id: Option<AccountId>,
     ) -> bool {
         assert_callback_calling();
 
-        let sp = &mut self.staking_pools[sp_inx];
+        let stake_succeeded = is_promise_success();
 
         //WARN: This is a callback after-cross-contract-call method
         //busy locks must be saved false in the state, this method SHOULD NOT PANIC
-        sp.busy_lock = false;
-        self.contract_busy = false;
+        if self.internal_end_pending_op(
+            sp_inx,
+            &PendingOp::Stake {
+                amount,
+                included_deposit,
+            },
+            lock_since,
+            contract_locked,
+        ) {
+            self.internal_apply_stake_result(sp_inx, amount, included_deposit, stake_succeeded);
+            if let (true, Some(keeper_id)) = (stake_succeeded, keeper_id) {
+                self.internal_pay_keeper_bounty(&keeper_id, "distribute_staking", 1);
+            }
+        }
 
-        let stake_succeeded = is_promise_success();
+        return stake_succeeded;
+    }
+
+    /// settles the preventive accounting of direct_stake
+    fn internal_apply_stake_result(
+        &mut self,
+        sp_inx: usize,
+        amount: u128,
+        included_deposit: bool,
+        stake_succeeded: bool,
+    ) {
+        let sp = &mut self.staking_pools[sp_inx];
 
         let result: &str;
         if stake_succeeded {
@@ -166,11 +251,11 @@ impl MetaPool {
             let event: &str;
             if included_deposit {
                 //we send NEAR to the staking-pool
-                event = "dist.stak"; //stake in the pools (including transfer)
+                event = "distribute_stake"; //stake in the pools (including transfer)
                                      //we took from contract balance (transfer)
                 self.contract_account_balance -= amount;
             } else {
-                event = "dist.stak.nt"; //stake in the pools, no-transfer
+                event = "distribute_stake_no_transfer"; //stake in the pools, no-transfer
                                         //not deposited first, so staked funds came from unstaked funds already in the staking-pool
                 sp.unstaked -= amount; //we've now less unstaked in this sp
                 self.total_unstaked_and_waiting -= amount; // contract total of all unstaked & waiting
@@ -181,12 +266,10 @@ impl MetaPool {
             //move into staked
             sp.staked += amount;
             //log event
-            event!(
-                r#"{{"event":"{}","sp":"{}","amount":"{}"}}"#,
-                event,
-                sp.account_id,
-                amount
-            );
+            event!(event, {
+                "sp": sp.account_id,
+                "amount": amount.to_string(),
+            });
         } else {
             //STAKE FAILED
             result = "has failed";
@@ -194,8 +277,30 @@ impl MetaPool {
             self.epoch_stake_orders += amount; //undo preventively reduce stake orders
         }
         log!("Staking of {} at @{} {}", amount, sp.account_id, result);
+    }
 
-        return stake_succeeded;
+    /// a callback settles the op in flight on its pool & releases the locks taken at lock_since.
+    /// Returns false if the op was already settled by resync_pool (the callback arrived after the lock expired)
+    fn internal_end_pending_op(
+        &mut self,
+        sp_inx: usize,
+        op: &PendingOp,
+        lock_since: BlockHeight,
+        contract_locked: bool,
+    ) -> bool {
+        if contract_locked {
+            self.internal_release_contract_lock(lock_since);
+        }
+        let sp = &mut self.staking_pools[sp_inx];
+        if &sp.pending_op != op || sp.pending_op_since != lock_since {
+            log!("op on @{} already settled by resync_pool", sp.account_id);
+            return false;
+        }
+        sp.pending_op = PendingOp::None;
+        //if the lock expired (needs_resync) it's not ours anymore, unlock is a no-op
+        sp.needs_resync = false;
+        sp.unlock(lock_since);
+        return true;
     }
 
     // execute stake on sp[inx] by amount
@@ -213,9 +318,10 @@ impl MetaPool {
         let sp_inx = inx as usize;
         assert!(sp_inx < self.staking_pools.len(), "invalid index");
         let sp = &self.staking_pools[sp_inx];
-        assert!(!sp.busy_lock, "sp busy");
+        assert!(!sp.is_busy(), "sp busy");
+        assert!(sp.evacuation_amount == 0, "sp is being evacuated");
         // perform direct stake
-        self.direct_stake(sp_inx, amount.0);
+        self.direct_stake(sp_inx, amount.0, true, None);
         // Note: if the pool has some sizable unstake pending, the fn will re-stake the unstaked-and-waiting-amount
         // that amount can be lower than the amount requested to stake
     }
@@ -228,7 +334,7 @@ impl MetaPool {
         let sp_inx = inx as usize;
         assert!(sp_inx < self.staking_pools.len(), "invalid index");
         let sp = &self.staking_pools[sp_inx];
-        assert!(!sp.busy_lock, "sp busy");
+        assert!(!sp.is_busy(), "sp busy");
         assert!(
             sp.weight_basis_points == 0,
             "sp.weight_basis_points<>0, ={}",
@@ -249,7 +355,7 @@ impl MetaPool {
         // epoch_unstake_orders should be >= to manual_unstake_amount
         // for direct_unstake to work
         self.epoch_unstake_orders += amount.0;
-        self.direct_unstake(sp_inx, amount.0);
+        self.direct_unstake(sp_inx, amount.0, true, UnstakeKind::Orders, None);
     }
     // this should be called by the operator
     // 4 EPOCHS AFTER MANUAL_UNSTAKE,
@@ -279,6 +385,94 @@ impl MetaPool {
         self.epoch_stake_orders += amount.0;
     }
 
+    // moves all the stake out of sp[inx], e.g. if a validator goes offline
+    // sets the weight to 0 (fixed with an override, the weight is spread on the other pools) and unstakes everything now.
+    // if the unstake fails, the weight and the override are restored
+    // Once the waiting period ends, the normal retrieve_funds_from_a_pool sends the funds
+    // to epoch_stake_orders, so they're re-staked in the other pools. No complete_manual_unstake required
+    pub fn evacuate_pool(&mut self, inx: u16) {
+        self.assert_operator_or_owner();
+        let sp_inx = inx as usize;
+        assert!(sp_inx < self.staking_pools.len(), "invalid index");
+        let sp = &mut self.staking_pools[sp_inx];
+        assert!(!sp.is_busy(), "sp busy");
+        assert!(sp.evacuation_amount == 0, "sp is already being evacuated");
+        assert!(sp.staked > 0, "sp has no stake");
+        // can not unstake while unstake pending because it will extend the waiting period
+        assert!(
+            sp.unstaked == 0,
+            "retrieve the pending unstake first. sp.unstake={}",
+            sp.unstaked,
+        );
+        let amount = sp.staked;
+        sp.evacuation_amount = amount;
+        let sp_account_id = sp.account_id.clone();
+        let kind = UnstakeKind::Evacuation {
+            prev_weight_basis_points: sp.weight_basis_points,
+            prev_override: self.internal_sp_weight_override(&sp_account_id),
+        };
+        // its weight goes to the other pools
+        assert!(
+            self.internal_set_sp_weight(sp_inx, 0),
+            "the weight of the pool can't be spread on the other pools"
+        );
+        // keep it at 0 for auto_rebalance_weights
+        self.sp_weight_overrides.retain(|item| item.0 != sp_account_id);
+        self.sp_weight_overrides.push((sp_account_id.clone(), 0));
+
+        event!("evacuation_start", {
+            "sp": sp_account_id,
+            "amount": amount.to_string(),
+        });
+        // epoch_unstake_orders should be >= to the amount for direct_unstake to work
+        self.epoch_unstake_orders += amount;
+        self.direct_unstake(sp_inx, amount, true, kind, None);
+    }
+
+    // operator method, active rebalancing after weight changes
+    // unstakes from the most over-weight pool, at most rebalance_max_bp_per_epoch of the total staked per epoch.
+    // Once retrieved, the funds go to epoch_stake_orders and distribute_staking re-stakes them into under-weight pools
+    // returns "true" if something was unstaked (call again)
+    pub fn rebalance_unstake(&mut self) -> bool {
+        self.assert_operator_or_owner();
+        self.assert_not_busy();
+        // user unstake orders first, they need the pools
+        if self.epoch_unstake_orders > 0 {
+            log!("epoch_unstake_orders pending, call distribute_unstaking first");
+            return false;
+        }
+
+        let epoch = env::epoch_height();
+        if self.rebalance_epoch != epoch {
+            self.rebalance_epoch = epoch;
+            self.rebalance_unstaked_this_epoch = 0;
+        }
+        let budget = apply_pct(self.rebalance_max_bp_per_epoch, self.total_actually_staked)
+            .saturating_sub(self.rebalance_unstaked_this_epoch);
+        if budget < MIN_STAKE_UNSTAKE_AMOUNT_MOVEMENT {
+            log!("rebalance budget for this epoch used");
+            return false;
+        }
+
+        let (sp_inx, amount) = self.get_staking_pool_requiring_rebalance(budget);
+        if amount == 0 {
+            log!("no pool requires rebalancing");
+            return false;
+        }
+
+        self.rebalance_unstaked_this_epoch += amount;
+        let sp = &mut self.staking_pools[sp_inx];
+        sp.evacuation_amount = amount;
+        event!("rebalance_unstake", {
+            "sp": sp.account_id,
+            "amount": amount.to_string(),
+        });
+        // epoch_unstake_orders should be >= to the amount for direct_unstake to work
+        self.epoch_unstake_orders += amount;
+        self.direct_unstake(sp_inx, amount, true, UnstakeKind::Rebalance { epoch }, None);
+        return true;
+    }
+
     // used by operator to reset epoch_stake/unstake_orders and restart staking/unstaking
     pub fn undo_end_of_epoch(&mut self) {
         self.assert_operator_or_owner();
@@ -321,20 +515,70 @@ impl MetaPool {
             return false;
         }
 
+        if self.max_parallel_pools > 1 {
+            return self.distribute_unstaking_parallel(total_to_unstake);
+        }
+
         let (sp_inx, amount_to_unstake) = self.get_staking_pool_requiring_unstake(total_to_unstake);
         if amount_to_unstake > 10 * TGAS as u128 {
             //only if the amount justifies tx-fee
             //most unbalanced pool found & available
-            //launch async to unstake
-            self.direct_unstake(sp_inx, amount_to_unstake);
+            //launch async to unstake, the keeper is paid by the callback
+            self.direct_unstake(
+                sp_inx,
+                amount_to_unstake,
+                true,
+                UnstakeKind::Orders,
+                Some(env::predecessor_account_id()),
+            );
             return true; //needs to be called again
         } else {
             return false;
         }
     }
 
+    // parallel mode (max_parallel_pools > 1): unstake on up to max_parallel_pools pools at once.
+    // only the pools are locked, each callback reconciles its own pool
+    fn distribute_unstaking_parallel(&mut self, total_to_unstake: u128) -> bool {
+        let gas_per_pool =
+            gas::staking_pool::UNSTAKE + gas::owner_callbacks::ON_STAKING_POOL_UNSTAKE;
+        let mut remaining = total_to_unstake;
+        let mut scheduled: u16 = 0;
+        while scheduled < self.max_parallel_pools
+            && remaining > 10 * TGAS as u128
+            && env::prepaid_gas() - env::used_gas() >= gas_per_pool + gas::BASE_GAS
+        {
+            // busy pools are skipped, so each iteration selects another pool
+            let (sp_inx, amount_to_unstake) = self.get_staking_pool_requiring_unstake(remaining);
+            if amount_to_unstake <= 10 * TGAS as u128 {
+                break;
+            }
+            self.direct_unstake(
+                sp_inx,
+                amount_to_unstake,
+                false,
+                UnstakeKind::Orders,
+                Some(env::predecessor_account_id()),
+            );
+            remaining -= amount_to_unstake;
+            scheduled += 1;
+        }
+        log!("unstaking scheduled on {} pools", scheduled);
+        return scheduled > 0;
+    }
+
     // execute unstake on sp[inx] by amount
-    fn direct_unstake(&mut self, sp_inx: usize, amount_to_unstake: u128) {
+    // lock_contract=false for parallel mode (only the pool is locked)
+    // kind: what to undo if the unstake fails
+    // keeper_id: the caller of the heartbeat fn, paid the keeper bounty if the unstake succeeds
+    fn direct_unstake(
+        &mut self,
+        sp_inx: usize,
+        amount_to_unstake: u128,
+        lock_contract: bool,
+        kind: UnstakeKind,
+        keeper_id: Option<AccountId>,
+    ) {
         if amount_to_unstake == 0 {
             return;
         }
@@ -354,8 +598,17 @@ impl MetaPool {
             amount_to_unstake
         );
 
-        self.contract_busy = true;
-        sp.busy_lock = true;
+        if lock_contract {
+            self.contract_busy = true;
+            self.contract_busy_since = env::block_index();
+        }
+        sp.lock();
+        let lock_since = sp.busy_lock_since;
+        sp.pending_op = PendingOp::Unstake {
+            amount: amount_to_unstake,
+            kind: kind.clone(),
+        };
+        sp.pending_op_since = lock_since;
 
         //preventively consider the amount un-staked (undoes if promise fails)
         self.total_actually_staked -= amount_to_unstake;
@@ -371,6 +624,10 @@ impl MetaPool {
         .then(ext_self_owner::on_staking_pool_unstake(
             sp_inx,
             amount_to_unstake.into(),
+            lock_contract,
+            lock_since,
+            kind,
+            keeper_id,
             //extra async call args
             &env::current_account_id(),
             NO_DEPOSIT,
@@ -380,37 +637,99 @@ impl MetaPool {
     /// The prev fn continues here
     /// Called after the given amount was unstaked at the staking pool contract.
     /// This method needs to update staking pool status.
-    pub fn on_staking_pool_unstake(&mut self, sp_inx: usize, amount: U128String) {
+    pub fn on_staking_pool_unstake(
+        &mut self,
+        sp_inx: usize,
+        amount: U128String,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        kind: UnstakeKind,
+        keeper_id: Option<AccountId>,
+    ) {
         assert_callback_calling();
 
-        let sp = &mut self.staking_pools[sp_inx];
-
         let unstake_succeeded = is_promise_success();
 
+        //WARN: This is a callback after-cross-contract-call method
+        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
+        if self.internal_end_pending_op(
+            sp_inx,
+            &PendingOp::Unstake {
+                amount: amount.0,
+                kind: kind.clone(),
+            },
+            lock_since,
+            contract_locked,
+        ) {
+            self.internal_apply_unstake_result(sp_inx, amount.0, kind, unstake_succeeded);
+            if let (true, Some(keeper_id)) = (unstake_succeeded, keeper_id) {
+                self.internal_pay_keeper_bounty(&keeper_id, "distribute_unstaking", 1);
+            }
+        }
+    }
+
+    /// settles the preventive accounting of direct_unstake
+    fn internal_apply_unstake_result(
+        &mut self,
+        sp_inx: usize,
+        amount: u128,
+        kind: UnstakeKind,
+        unstake_succeeded: bool,
+    ) {
+        let sp = &mut self.staking_pools[sp_inx];
+
         let result: &str;
         if unstake_succeeded {
             result = "succeeded";
-            sp.staked -= amount.0;
-            sp.unstaked += amount.0;
+            sp.staked -= amount;
+            sp.unstaked += amount;
             sp.unstk_req_epoch_height = env::epoch_height();
-            self.total_unstaked_and_waiting += amount.0; //contract total
-            event!(
-                r#"{{"event":"dist.unstk","sp":"{}","amount":"{}"}}"#,
-                sp.account_id,
-                amount.0
-            );
+            self.total_unstaked_and_waiting += amount; //contract total
+            event!("distribute_unstake", {
+                "sp": sp.account_id,
+                "amount": amount.to_string(),
+            });
         } else {
             result = "has failed";
-            self.total_actually_staked += amount.0; //undo preventive action considering the amount unstaked
-            self.epoch_unstake_orders += amount.0; //undo preventive action considering the amount unstaked
+            self.total_actually_staked += amount; //undo preventive action considering the amount unstaked
+            match kind {
+                UnstakeKind::Orders => {
+                    self.epoch_unstake_orders += amount; //undo preventive action considering the amount unstaked
+                }
+                UnstakeKind::Rebalance { epoch } => {
+                    //the orders were not from users, the rebalance is cancelled
+                    sp.evacuation_amount = 0;
+                    //refund the epoch budget
+                    if self.rebalance_epoch == epoch {
+                        self.rebalance_unstaked_this_epoch =
+                            self.rebalance_unstaked_this_epoch.saturating_sub(amount);
+                    }
+                }
+                UnstakeKind::Evacuation {
+                    prev_weight_basis_points,
+                    prev_override,
+                } => {
+                    //the orders were not from users, the evacuation is cancelled: restore weight & override
+                    sp.evacuation_amount = 0;
+                    let sp_account_id = sp.account_id.clone();
+                    self.sp_weight_overrides.retain(|item| item.0 != sp_account_id);
+                    if let Some(bp) = prev_override {
+                        self.sp_weight_overrides.push((sp_account_id.clone(), bp));
+                    }
+                    if !self.internal_set_sp_weight(sp_inx, prev_weight_basis_points) {
+                        //the pool stays at weight 0, the operator can set the weights with set_staking_pools
+                        log!("weight {} of sp {} can't be restored", prev_weight_basis_points, sp_account_id);
+                    }
+                }
+            }
         }
 
-        log!("Unstaking of {} at @{} {}", amount.0, sp.account_id, result);
-
-        //WARN: This is a callback after-cross-contract-call method
-        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
-        sp.busy_lock = false;
-        self.contract_busy = false;
+        log!(
+            "Unstaking of {} at @{} {}",
+            amount,
+            self.staking_pools[sp_inx].account_id,
+            result
+        );
     }
 
     //utility to set contract busy flag manually by operator.
@@ -419,6 +738,7 @@ impl MetaPool {
         assert_one_yocto();
         self.assert_operator_or_owner();
         self.contract_busy = value;
+        self.contract_busy_since = env::block_index();
     }
     //operator manual set sp.busy_lock
     #[payable]
@@ -430,7 +750,179 @@ impl MetaPool {
         assert!(inx < self.staking_pools.len());
 
         let sp = &mut self.staking_pools[inx];
-        sp.busy_lock = value;
+        if value {
+            sp.lock();
+        } else {
+            sp.busy_lock = false;
+            //an op in flight whose callback never ran: the pool must be resynced
+            if !sp.pending_op.is_none() {
+                sp.needs_resync = true;
+            }
+        }
+    }
+
+    /// public method: releases the contract & pool locks taken more than busy_lock_expiry_blocks ago
+    /// (their callbacks never ran). Returns how many locks were released.
+    /// A pool released with an op in flight is marked needs_resync: its preventive accounting was not settled,
+    /// the pool can't be used until resync_pool reads its real balances
+    pub fn clear_stale_locks(&mut self) -> u16 {
+        return self.internal_clear_stale_locks();
+    }
+
+    /// resync_pool: open to anyone. For a pool marked needs_resync by clear_stale_locks,
+    /// reads the real staked & unstaked balances from the pool and settles the op that was in flight
+    pub fn resync_pool(&mut self, sp_inx: u16) -> Promise {
+        let inx = sp_inx as usize;
+        assert!(inx < self.staking_pools.len(), "invalid index");
+
+        let sp = &mut self.staking_pools[inx];
+        assert!(sp.needs_resync, "sp {} does not need a resync", inx);
+        assert!(!sp.busy_lock, "sp is busy");
+        sp.lock();
+
+        return ext_staking_pool::get_account(
+            env::current_account_id(),
+            //promise params
+            &sp.account_id,
+            NO_DEPOSIT,
+            gas::staking_pool::GET_ACCOUNT,
+        )
+        .then(ext_self_owner::on_resync_pool(
+            inx,
+            sp.busy_lock_since,
+            //promise params
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            gas::owner_callbacks::ON_RESYNC_POOL,
+        ));
+    }
+
+    /// prev fn continues here - resync_pool
+    /// Decides from the real balances if the op in flight succeeded:
+    /// re-stake, unstake & withdraw: the unstaked balance (it gets no rewards) moved by at least half the amount.
+    /// deposit & stake: the staked balance also moves with rewards, so only if the pool rewards were distributed
+    /// in this epoch and the staked balance is exactly sp.staked (failed) or sp.staked+amount (succeeded).
+    /// Else the op stays pending and the operator settles it with resolve_pending_op
+    pub fn on_resync_pool(&mut self, sp_inx: usize, lock_since: BlockHeight) {
+        assert_callback_calling();
+
+        // not #[callback], the pool must be unlocked if the view call fails
+        let account = match env::promise_result(0) {
+            PromiseResult::Successful(data) => {
+                near_sdk::serde_json::from_slice::<HumanReadableAccount>(&data).ok()
+            }
+            _ => None,
+        };
+
+        //WARN: This is a callback after-cross-contract-call method
+        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
+        let sp = &mut self.staking_pools[sp_inx];
+        if !sp.unlock(lock_since) {
+            log!("stale resync of @{} ignored, the lock expired", sp.account_id);
+            return;
+        }
+        let account = match account {
+            Some(account) => account,
+            None => {
+                log!("could not get the account from @{}", sp.account_id);
+                return;
+            }
+        };
+        if !sp.needs_resync {
+            //the callback of the op arrived meanwhile and settled it
+            return;
+        }
+        let real_staked = account.staked_balance.0;
+        let real_unstaked = account.unstaked_balance.0;
+
+        let succeeded = match &sp.pending_op {
+            PendingOp::None => Some(true),
+            PendingOp::Stake {
+                amount,
+                included_deposit: true,
+            } => {
+                if sp.last_asked_rewards_epoch_height != env::epoch_height() {
+                    None
+                } else if is_close(real_staked, sp.staked + amount) {
+                    Some(true)
+                } else if is_close(real_staked, sp.staked) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            PendingOp::Stake {
+                amount,
+                included_deposit: false,
+            } => Some(real_unstaked + amount / 2 <= sp.unstaked),
+            PendingOp::Unstake { amount, .. } => Some(real_unstaked >= sp.unstaked + amount / 2),
+            PendingOp::Withdraw { amount } => Some(real_unstaked + amount / 2 <= sp.unstaked),
+        };
+        let succeeded = match succeeded {
+            Some(succeeded) => succeeded,
+            None => {
+                event!("resync_undecided", {
+                    "sp": sp.account_id,
+                    "staked": sp.staked.to_string(),
+                    "real_staked": real_staked.to_string(),
+                });
+                return;
+            }
+        };
+        self.internal_settle_pending_op(sp_inx, succeeded);
+        // the rounding differences
+        self.internal_sync_sp_unstaked(sp_inx, real_unstaked);
+
+        let sp = &self.staking_pools[sp_inx];
+        event!("pool_resynced", {
+            "sp": sp.account_id,
+            "staked": sp.staked.to_string(),
+            "unstaked": sp.unstaked.to_string(),
+        });
+    }
+
+    /// operator method, for a pool resync_pool could not settle (resync_undecided event):
+    /// settles the op that was in flight with the result of its receipt (check it in the explorer)
+    #[payable]
+    pub fn resolve_pending_op(&mut self, sp_inx: u16, succeeded: bool) {
+        assert_one_yocto();
+        self.assert_operator_or_owner();
+        let inx = sp_inx as usize;
+        assert!(inx < self.staking_pools.len(), "invalid index");
+        let sp = &self.staking_pools[inx];
+        assert!(sp.needs_resync, "sp {} does not need a resync", inx);
+        assert!(!sp.busy_lock, "sp is busy");
+
+        self.internal_settle_pending_op(inx, succeeded);
+
+        let sp = &self.staking_pools[inx];
+        event!("pool_resynced", {
+            "sp": sp.account_id,
+            "staked": sp.staked.to_string(),
+            "unstaked": sp.unstaked.to_string(),
+            "resolved_by": env::predecessor_account_id(),
+        });
+    }
+
+    /// applies the result of the op the pool had in flight & clears needs_resync
+    fn internal_settle_pending_op(&mut self, sp_inx: usize, succeeded: bool) {
+        let sp = &mut self.staking_pools[sp_inx];
+        sp.needs_resync = false;
+        match std::mem::take(&mut sp.pending_op) {
+            PendingOp::None => {}
+            PendingOp::Stake {
+                amount,
+                included_deposit,
+            } => {
+                self.internal_apply_stake_result(sp_inx, amount, included_deposit, succeeded);
+            }
+            PendingOp::Unstake { amount, kind } => {
+                self.internal_apply_unstake_result(sp_inx, amount, kind, succeeded);
+            }
+            PendingOp::Withdraw { amount } => {
+                self.internal_apply_withdraw_result(sp_inx, amount, succeeded);
+            }
+        }
     }
 
     //-- check If extra balance has accumulated (30% of tx fees by near-protocol)
@@ -468,7 +960,7 @@ impl MetaPool {
 
         self.assert_not_busy();
         let sp = &mut self.staking_pools[inx];
-        assert!(!sp.busy_lock, "sp is busy");
+        assert!(!sp.is_busy(), "sp is busy");
 
         // SUGGESTION: Maybe better to call `get_account` to get information about `staked` and
         //    `unstaked` balance at the same time. Sometimes the staking pool may throw yoctoNEAR
@@ -510,7 +1002,7 @@ impl MetaPool {
 
         assert_callback_calling();
 
-        let sp = &mut self.staking_pools[sp_inx];
+        let sp = &self.staking_pools[sp_inx];
 
         // real unstaked amount for this pool
         let real_unstaked_balance: u128 = unstaked_balance.0;
@@ -524,11 +1016,17 @@ impl MetaPool {
         );
         // we're not locking at the start, so we check there's no in-flight transaction if we need to
         // adjust the unstaked in a few yoctos
-        if real_unstaked_balance != sp.unstaked && (self.contract_busy || sp.busy_lock) {
+        if real_unstaked_balance != sp.unstaked && (self.contract_busy || sp.is_busy()) {
             // do not proceed to update if another operation is in mid-flight
             panic!("cant not update unstaked, contract or sp is busy, another operation is in mid-flight");
         }
 
+        self.internal_sync_sp_unstaked(sp_inx, real_unstaked_balance);
+    }
+
+    /// sets sp.unstaked to the real unstaked amount informed by the sp, the difference goes to sp.staked
+    fn internal_sync_sp_unstaked(&mut self, sp_inx: usize, real_unstaked_balance: u128) {
+        let sp = &mut self.staking_pools[sp_inx];
         if real_unstaked_balance > sp.unstaked {
             //positive difference
             let difference = real_unstaked_balance - sp.unstaked;
@@ -544,6 +1042,80 @@ impl MetaPool {
         }
     }
 
+    //-------------------------
+    /// check_pool_fee: open to anyone. Queries the validator fee of the pool and records it.
+    /// If the fee is above max_sp_fee_basis_points, the pool weight is set to 0 (and fixed with an override)
+    /// so distribute_unstaking drains it. Its weight is spread on the other pools
+    pub fn check_pool_fee(&mut self, sp_inx: u16) -> Promise {
+        let inx = sp_inx as usize;
+        assert!(inx < self.staking_pools.len());
+        let sp = &self.staking_pools[inx];
+        assert!(!sp.is_busy(), "sp is busy");
+
+        return ext_staking_pool::get_reward_fee_fraction(
+            //promise params
+            &sp.account_id,
+            NO_DEPOSIT,
+            gas::staking_pool::GET_REWARD_FEE_FRACTION,
+        )
+        .then(ext_self_owner::on_get_sp_reward_fee_fraction(
+            sp.account_id.clone(),
+            //promise params
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            gas::owner_callbacks::ON_GET_SP_REWARD_FEE_FRACTION,
+        ));
+    }
+
+    /// prev fn continues here - check_pool_fee
+    //------------------------------
+    pub fn on_get_sp_reward_fee_fraction(
+        &mut self,
+        sp_account_id: AccountId,
+        #[callback] reward_fee_fraction: RewardFeeFraction,
+    ) {
+        // Note: no locks here, if the pool view call fails this callback is not entered (#[callback])
+        // the pool is looked up by account, indexes can change while the call is in flight
+        assert_callback_calling();
+
+        let fee_basis_points = reward_fee_fraction.basis_points();
+        let mut perf = self.sp_performance.get(&sp_account_id).unwrap_or_default();
+        perf.fee_basis_points = fee_basis_points;
+        perf.fee_checked_epoch = env::epoch_height();
+        self.sp_performance.insert(&sp_account_id, &perf);
+
+        if fee_basis_points <= self.max_sp_fee_basis_points {
+            return;
+        }
+        let sp_inx = match self
+            .staking_pools
+            .iter()
+            .position(|sp| sp.account_id == sp_account_id)
+        {
+            Some(inx) => inx,
+            None => return, // removed meanwhile
+        };
+        if self.staking_pools[sp_inx].is_busy() {
+            // don't change a pool while a stake/unstake is in flight, check_pool_fee can be called again
+            log!("sp {} is busy, not demoted", sp_account_id);
+            return;
+        }
+        let prev_weight = self.staking_pools[sp_inx].weight_basis_points;
+        if !self.internal_set_sp_weight(sp_inx, 0) {
+            log!("the weight of sp {} can't be spread on the other pools, not demoted", sp_account_id);
+            return;
+        }
+        // keep it at 0 for auto_rebalance_weights, the owner can remove the override
+        self.sp_weight_overrides.retain(|item| item.0 != sp_account_id);
+        self.sp_weight_overrides.push((sp_account_id.clone(), 0));
+        event!("sp_fee_demote", {
+            "sp": sp_account_id,
+            "fee_basis_points": fee_basis_points,
+            "max_fee_basis_points": self.max_sp_fee_basis_points,
+            "prev_weight_basis_points": prev_weight,
+        });
+    }
+
     //------------------------------------------------------------------------
     //-- COMPUTE AND DISTRIBUTE STAKING REWARDS for a specific staking-pool --
     //------------------------------------------------------------------------
@@ -562,7 +1134,7 @@ impl MetaPool {
         assert!(inx < self.staking_pools.len());
 
         let sp = &mut self.staking_pools[inx];
-        assert!(!sp.busy_lock, "sp is busy");
+        assert!(!sp.is_busy(), "sp is busy");
 
         let epoch_height = env::epoch_height();
 
@@ -580,7 +1152,8 @@ impl MetaPool {
         );
 
         self.contract_busy = true;
-        sp.busy_lock = true;
+        self.contract_busy_since = env::block_index();
+        sp.lock();
 
         //query our current balance (includes staked+unstaked+staking rewards)
         ext_staking_pool::get_account_total_balance(
@@ -592,6 +1165,8 @@ impl MetaPool {
         )
         .then(ext_self_owner::on_get_sp_total_balance(
             inx,
+            sp.busy_lock_since,
+            env::predecessor_account_id(),
             //promise params
             &env::current_account_id(),
             NO_DEPOSIT,
@@ -599,6 +1174,113 @@ impl MetaPool {
         ));
     }
 
+    //------------------------------------------------------------------------
+    //-- COMPUTE AND DISTRIBUTE STAKING REWARDS for several staking-pools  --
+    //------------------------------------------------------------------------
+    // Operator method, but open to anyone. Same as distribute_rewards for the pools in [from, from+limit)
+    /// queries all the pools with joined promises and settles them in one callback.
+    /// Only the pools involved are locked, not the contract. Pools busy, empty or already asked this epoch are skipped
+    /// returns how many pools were queried
+    pub fn distribute_rewards_batch(&mut self, from: u16, limit: u16) -> u16 {
+        assert!(
+            limit <= MAX_DISTRIBUTE_REWARDS_BATCH,
+            "limit max is {}",
+            MAX_DISTRIBUTE_REWARDS_BATCH
+        );
+        let epoch_height = env::epoch_height();
+        let end = std::cmp::min(from as usize + limit as usize, self.staking_pools.len());
+
+        let mut sp_inxs: Vec<u16> = Vec::new();
+        let mut promise: Option<Promise> = None;
+        for inx in (from as usize)..end {
+            let sp = &mut self.staking_pools[inx];
+            if sp.is_busy()
+                || (sp.staked == 0 && sp.unstaked == 0)
+                || sp.last_asked_rewards_epoch_height == epoch_height
+            {
+                continue;
+            }
+            sp.lock();
+            sp_inxs.push(inx as u16);
+            //query our current balance (includes staked+unstaked+staking rewards)
+            let query = ext_staking_pool::get_account_total_balance(
+                env::current_account_id(),
+                //promise params
+                &sp.account_id,
+                NO_DEPOSIT,
+                gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE_BATCH,
+            );
+            promise = Some(match promise {
+                None => query,
+                Some(joined) => joined.and(query),
+            });
+        }
+
+        let count = sp_inxs.len() as u16;
+        if let Some(joined) = promise {
+            let callback_gas = gas::owner_callbacks::ON_GET_SP_TOTAL_BALANCE_BATCH_BASE
+                + count as u64 * gas::owner_callbacks::ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL;
+            assert!(
+                env::prepaid_gas()
+                    >= count as u64 * gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE_BATCH
+                        + callback_gas
+                        + gas::BASE_GAS,
+                "not enough gas for {} pools",
+                count
+            );
+            joined.then(ext_self_owner::on_get_sp_total_balance_batch(
+                sp_inxs,
+                env::block_index(),
+                env::predecessor_account_id(),
+                //promise params
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                callback_gas,
+            ));
+        }
+        return count;
+    }
+
+    /// prev fn continues here - distribute_rewards_batch
+    /// one promise result per pool in sp_inxs, a failed query only skips that pool.
+    /// The pools were locked at lock_since, a pool whose lock expired meanwhile is skipped
+    /// returns how many pools were settled, the keeper is paid for those
+    pub fn on_get_sp_total_balance_batch(
+        &mut self,
+        sp_inxs: Vec<u16>,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> u16 {
+        assert_callback_calling();
+
+        //WARN: This is a callback after-cross-contract-call method
+        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
+        let mut settled: u16 = 0;
+        for (result_inx, inx) in sp_inxs.iter().enumerate() {
+            let sp_inx = *inx as usize;
+            if !self.staking_pools[sp_inx].unlock(lock_since) {
+                log!(
+                    "stale total balance of @{} ignored, the lock expired",
+                    self.staking_pools[sp_inx].account_id
+                );
+                continue;
+            }
+            if let PromiseResult::Successful(data) = env::promise_result(result_inx as u64) {
+                if let Ok(total_balance) = near_sdk::serde_json::from_slice::<U128String>(&data) {
+                    self.internal_settle_sp_total_balance(sp_inx, total_balance.0);
+                    settled += 1;
+                    continue;
+                }
+            }
+            log!(
+                "could not get the total balance from @{}",
+                self.staking_pools[sp_inx].account_id
+            );
+        }
+        self.internal_pay_keeper_bounty(&keeper_id, "distribute_rewards", settled);
+        return settled;
+    }
+
     /// prev fn continues here
     /*
     Note: what does the tag #[callback] applied to a fn in parameter do?
@@ -639,6 +1321,8 @@ impl MetaPool {
     pub fn on_get_sp_total_balance(
         &mut self,
         sp_inx: usize,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
         #[callback] total_balance: U128String,
     ) {
         //we enter here after asking the staking-pool how much do we have staked (plus rewards)
@@ -646,27 +1330,48 @@ impl MetaPool {
 
         assert_callback_calling();
 
-        //new_total_balance has the new staked amount for this pool
-        let new_total_balance: u128;
-        let sp = &mut self.staking_pools[sp_inx];
-
         //WARN: This is a callback after-cross-contract-call method
         //busy locks must be saved false in the state, this method SHOULD NOT PANIC
-        sp.busy_lock = false;
-        self.contract_busy = false;
-
-        sp.last_asked_rewards_epoch_height = env::epoch_height();
+        //the locks were taken at lock_since, if they expired meanwhile they're not ours anymore
+        self.internal_release_contract_lock(lock_since);
+        if !self.staking_pools[sp_inx].unlock(lock_since) {
+            log!(
+                "stale total balance of @{} ignored, the lock expired",
+                self.staking_pools[sp_inx].account_id
+            );
+            return;
+        }
 
         //total_balance informed is staking-pool.staked + staking-pool.unstaked
-        new_total_balance = total_balance.0;
+        self.internal_settle_sp_total_balance(sp_inx, total_balance.0);
+        self.internal_pay_keeper_bounty(&keeper_id, "distribute_rewards", 1);
+    }
+
+    /// updates the sp staked amount from the total balance informed by the staking-pool,
+    /// computes and distributes rewards or books a loss
+    /// WARN: called from callbacks, SHOULD NOT PANIC
+    fn internal_settle_sp_total_balance(&mut self, sp_inx: usize, new_total_balance: u128) {
+        let sp = &mut self.staking_pools[sp_inx];
+
+        let prev_asked_epoch = sp.last_asked_rewards_epoch_height;
+        sp.last_asked_rewards_epoch_height = env::epoch_height();
+        let staked_before = sp.staked;
 
         let rewards: u128;
+        let mut loss: u128 = 0;
         if new_total_balance < sp.total_balance() {
-            log!(
-                "INCONSISTENCY @{} says new_total_balance < our info sp.total_balance()",
-                sp.account_id
-            );
+            //LOSS (slashing or rounding), the shortfall is taken from the staked amount
             rewards = 0;
+            let shortfall = sp.total_balance() - new_total_balance;
+            loss = std::cmp::min(shortfall, sp.staked);
+            if loss < shortfall {
+                log!(
+                    "INCONSISTENCY @{} loss {} is more than staked {}",
+                    sp.account_id,
+                    shortfall,
+                    sp.staked
+                );
+            }
         } else {
             //compute rewards, as new balance minus old balance
             rewards = new_total_balance - sp.total_balance();
@@ -680,10 +1385,23 @@ impl MetaPool {
             rewards
         );
 
+        event!("rewards", {
+            "sp": sp.account_id,
+            "old_balance": sp.total_balance().to_string(),
+            "new_balance": new_total_balance.to_string(),
+            "rewards": rewards.to_string(),
+        });
+
         //updated accumulated_staked_rewards value for the contract
         self.accumulated_staked_rewards += rewards;
         //updated new "staked" value for this pool
-        sp.staked = new_total_balance - sp.unstaked;
+        if loss > 0 {
+            sp.staked -= loss;
+            let sp_account_id = sp.account_id.clone();
+            self.internal_book_staking_loss(&sp_account_id, loss);
+        } else {
+            sp.staked = new_total_balance.saturating_sub(sp.unstaked);
+        }
 
         if rewards > 0 {
             //add to total_for_staking & total_actually_staked, increasing share value for all stNEAR holders
@@ -697,9 +1415,13 @@ impl MetaPool {
             // The fee the contract authors take from rewards (0.2%)
             let developers_fee = apply_pct(DEVELOPERS_REWARDS_FEE_BASIS_POINTS, rewards);
             let developers_fee_shares = self.stake_shares_from_amount(developers_fee);
+            // The insurance fund fee
+            let insurance_fee = apply_pct(self.insurance_rewards_fee_basis_points, rewards);
+            let insurance_fee_shares = self.stake_shares_from_amount(insurance_fee);
             // Now add the newly minted shares. The fee is taken by making share price increase slightly smaller
             self.add_extra_minted_shares(self.operator_account_id.clone(), operator_fee_shares);
             self.add_extra_minted_shares(DEVELOPERS_ACCOUNT_ID.into(), developers_fee_shares);
+            self.add_extra_minted_shares(INSURANCE_INTERNAL_ACCOUNT.into(), insurance_fee_shares);
 
             // estimate $META rewards to stakers
             self.est_meta_rewards_stakers += damp_multiplier(
@@ -709,6 +1431,46 @@ impl MetaPool {
                 self.max_meta_rewards_stakers,
             );
         }
+
+        //keep the pool performance & the stNEAR price history
+        let sp_account_id = self.staking_pools[sp_inx].account_id.clone();
+        self.internal_record_sp_rewards(&sp_account_id, prev_asked_epoch, staked_before, rewards);
+        self.internal_record_price_snapshot(rewards);
+    }
+
+    /// books a loss reported by a staking pool (sp.staked already reduced)
+    /// the loss is covered first by burning insurance fund stNEAR up to loss_coverage_cap,
+    /// the rest is socialized: total_for_staking decreases, so the stNEAR price decreases
+    /// WARN: called from a callback, SHOULD NOT PANIC
+    pub(crate) fn internal_book_staking_loss(&mut self, sp_account_id: &AccountId, loss: u128) {
+        self.total_actually_staked = self.total_actually_staked.saturating_sub(loss);
+
+        // insurance: burn insurance stNEAR valued at the covered amount, so the price is not affected by that part
+        let insurance_account_id: AccountId = INSURANCE_INTERNAL_ACCOUNT.into();
+        let mut insurance_account = self.internal_get_account(&insurance_account_id);
+        let insurance_valued = self.amount_from_stake_shares(insurance_account.stake_shares);
+        let covered = std::cmp::min(std::cmp::min(loss, self.loss_coverage_cap), insurance_valued);
+        let mut shares_burned: u128 = 0;
+        if covered > 0 {
+            shares_burned = std::cmp::min(
+                self.stake_shares_from_amount(covered),
+                insurance_account.stake_shares,
+            );
+            insurance_account.sub_stake_shares(shares_burned, covered);
+            self.internal_update_account(&insurance_account_id, &insurance_account);
+            self.total_stake_shares = self.total_stake_shares.saturating_sub(shares_burned);
+            log_ft_burn(INSURANCE_INTERNAL_ACCOUNT, shares_burned);
+        }
+        // all the stNEAR holders absorb the rest
+        self.total_for_staking = self.total_for_staking.saturating_sub(loss);
+
+        event!("loss", {
+            "sp": sp_account_id,
+            "loss": loss.to_string(),
+            "covered": covered.to_string(),
+            "shares_burned": shares_burned.to_string(),
+            "socialized": (loss - covered).to_string(),
+        });
     }
 
     //----------------------------------------------------------------------
@@ -733,7 +1495,7 @@ impl MetaPool {
                     if result == -2 {
                         result = -1
                     };
-                    if !sp.busy_lock {
+                    if !sp.is_busy() {
                         // if this pool has unstaked and the waiting period has ended
                         return sp_inx as i32;
                     }
@@ -760,7 +1522,7 @@ impl MetaPool {
         self.assert_not_busy();
 
         let sp = &mut self.staking_pools[inx as usize];
-        assert!(!sp.busy_lock, "sp is busy");
+        assert!(!sp.is_busy(), "sp is busy");
         assert!(sp.unstaked > 0, "sp unstaked == 0");
         if !sp.wait_period_ended() {
             panic!(
@@ -773,10 +1535,14 @@ impl MetaPool {
         // if we're here, the pool is not busy, and we unstaked and the waiting period has elapsed
 
         self.contract_busy = true;
-        sp.busy_lock = true;
-
-        //return promise
-        return ext_staking_pool::withdraw(
+        self.contract_busy_since = env::block_index();
+        sp.lock();
+        sp.pending_op = PendingOp::Withdraw {
+            amount: sp.unstaked,
+        };
+        sp.pending_op_since = sp.busy_lock_since;
+
+        let promise = ext_staking_pool::withdraw(
             sp.unstaked.into(),
             //promise params:
             &sp.account_id,
@@ -785,22 +1551,58 @@ impl MetaPool {
         )
         .then(ext_self_owner::on_retrieve_from_staking_pool(
             inx,
+            sp.unstaked.into(),
+            true,
+            sp.busy_lock_since,
+            env::predecessor_account_id(),
             //promise params:
             &env::current_account_id(),
             NO_DEPOSIT,
             gas::owner_callbacks::ON_STAKING_POOL_WITHDRAW,
         ));
+        return promise;
     }
     //prev fn continues here
     /// This method needs to update staking pool busyLock
-    pub fn on_retrieve_from_staking_pool(&mut self, inx: u16) -> U128String {
+    pub fn on_retrieve_from_staking_pool(
+        &mut self,
+        inx: u16,
+        amount: U128String,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> U128String {
         assert_callback_calling();
 
-        let sp = &mut self.staking_pools[inx as usize];
-        let amount = sp.unstaked; //we retrieved all
-
         let withdraw_succeeded = is_promise_success();
 
+        //WARN: This is a callback after-cross-contract-call method
+        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
+        if !self.internal_end_pending_op(
+            inx as usize,
+            &PendingOp::Withdraw { amount: amount.0 },
+            lock_since,
+            contract_locked,
+        ) {
+            return 0.into();
+        }
+        let withdrawn_amount =
+            self.internal_apply_withdraw_result(inx as usize, amount.0, withdraw_succeeded);
+        if withdrawn_amount > 0 {
+            self.internal_pay_keeper_bounty(&keeper_id, "retrieve_funds_from_a_pool", 1);
+        }
+        return withdrawn_amount.into();
+    }
+
+    /// settles a withdraw of the pool unstaked balance. Returns the amount withdrawn
+    fn internal_apply_withdraw_result(
+        &mut self,
+        sp_inx: usize,
+        amount: u128,
+        withdraw_succeeded: bool,
+    ) -> u128 {
+        let sp = &mut self.staking_pools[sp_inx];
+
         let result: &str;
         let withdrawn_amount: u128;
         if withdraw_succeeded {
@@ -811,14 +1613,23 @@ impl MetaPool {
                 self.total_unstaked_and_waiting.saturating_sub(amount); //contract total
                                                                         // the amount is now in the contract balance
             self.contract_account_balance += amount;
-            // the amount retrieved should be "reserved_for_unstaked_claims" until the user calls withdraw_unstaked
-            self.reserve_for_unstake_claims += amount;
+            // evacuated funds are re-staked in the other pools
+            let evacuated = std::cmp::min(sp.evacuation_amount, amount);
+            if sp.evacuation_amount > 0 {
+                sp.evacuation_amount = 0;
+                self.epoch_stake_orders += evacuated;
+                event!("evacuation_complete", {
+                    "sp": sp.account_id,
+                    "amount": evacuated.to_string(),
+                });
+            }
+            // the rest retrieved should be "reserved_for_unstaked_claims" until the user calls withdraw_unstaked
+            self.reserve_for_unstake_claims += amount - evacuated;
             //log event
-            event!(
-                r#"{{"event":"retrieve","sp":"{}","amount":"{}"}}"#,
-                sp.account_id,
-                amount
-            );
+            event!("retrieve", {
+                "sp": sp.account_id,
+                "amount": amount.to_string(),
+            });
         } else {
             result = "has failed";
             withdrawn_amount = 0;
@@ -830,12 +1641,145 @@ impl MetaPool {
             result
         );
 
-        //WARN: This is a callback after-cross-contract-call method
-        //busy locks must be saved false in the state, this method SHOULD NOT PANIC
-        sp.busy_lock = false;
-        self.contract_busy = false;
+        return withdrawn_amount;
+    }
 
-        return withdrawn_amount.into();
+    // Operator method, but open to anyone
+    //----------------------------------------------------------------------
+    //  WITHDRAW FROM ALL THE POOLS WITH THE WAITING PERIOD ELAPSED
+    //----------------------------------------------------------------------
+    /// for every pool with matured unstaked funds: withdraw_all + query the unstaked balance left, all joined,
+    /// then one callback settles the withdrawals and syncs sp.unstaked. Only the pools are locked.
+    /// Pools are added while there's gas, call again if some were left out
+    /// returns a per-pool report of what was retrieved (empty if no pool was ready)
+    pub fn retrieve_all_matured(&mut self) -> PromiseOrValue<Vec<RetrieveReportJSON>> {
+        let gas_per_pool = gas::staking_pool::WITHDRAW_ALL_BATCH
+            + gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE_BATCH;
+        let mut sp_inxs: Vec<u16> = Vec::new();
+        let mut amounts: Vec<U128String> = Vec::new();
+        let mut promise: Option<Promise> = None;
+        for inx in 0..self.staking_pools.len() {
+            let gas_left = env::prepaid_gas() - env::used_gas();
+            if gas_left
+                < (sp_inxs.len() as u64 + 1) * gas::owner_callbacks::ON_RETRIEVE_ALL_MATURED_PER_POOL
+                    + gas_per_pool
+                    + gas::owner_callbacks::ON_RETRIEVE_ALL_MATURED
+                    + gas::BASE_GAS
+            {
+                break;
+            }
+            let sp = &mut self.staking_pools[inx];
+            //if the amount to retrieve justifies the tx-fee
+            if sp.is_busy() || sp.unstaked <= 10 * TGAS as u128 || !sp.wait_period_ended() {
+                continue;
+            }
+            sp.lock();
+            sp.pending_op = PendingOp::Withdraw {
+                amount: sp.unstaked,
+            };
+            sp.pending_op_since = sp.busy_lock_since;
+            sp_inxs.push(inx as u16);
+            amounts.push(sp.unstaked.into());
+            //withdraw all (sp.unstaked can be a few yoctos off), then query what's left unstaked
+            let chain = ext_staking_pool::withdraw_all(
+                //promise params
+                &sp.account_id,
+                NO_DEPOSIT,
+                gas::staking_pool::WITHDRAW_ALL_BATCH,
+            )
+            .then(ext_staking_pool::get_account_unstaked_balance(
+                env::current_account_id(),
+                //promise params
+                &sp.account_id,
+                NO_DEPOSIT,
+                gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE_BATCH,
+            ));
+            promise = Some(match promise {
+                None => chain,
+                Some(joined) => joined.and(chain),
+            });
+        }
+
+        let joined = match promise {
+            None => return PromiseOrValue::Value(Vec::new()),
+            Some(joined) => joined,
+        };
+        let count = sp_inxs.len() as u16;
+        let callback_gas = gas::owner_callbacks::ON_RETRIEVE_ALL_MATURED
+            + count as u64 * gas::owner_callbacks::ON_RETRIEVE_ALL_MATURED_PER_POOL;
+        let result = joined.then(ext_self_owner::on_retrieve_all_matured(
+            sp_inxs,
+            amounts,
+            env::block_index(),
+            env::predecessor_account_id(),
+            //promise params
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            callback_gas,
+        ));
+        return PromiseOrValue::Promise(result);
+    }
+
+    /// prev fn continues here - retrieve_all_matured
+    /// one promise result per pool in sp_inxs: the unstaked balance left after withdraw_all.
+    /// The withdraw succeeded if at least half of amount left the pool
+    pub fn on_retrieve_all_matured(
+        &mut self,
+        sp_inxs: Vec<u16>,
+        amounts: Vec<U128String>,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> Vec<RetrieveReportJSON> {
+        assert_callback_calling();
+
+        let mut retrieved_count: u16 = 0;
+        let mut report: Vec<RetrieveReportJSON> = Vec::with_capacity(sp_inxs.len());
+        for (result_inx, inx) in sp_inxs.iter().enumerate() {
+            let sp_inx = *inx as usize;
+            let amount = amounts[result_inx].0;
+            let unstaked_balance = match env::promise_result(result_inx as u64) {
+                PromiseResult::Successful(data) => {
+                    near_sdk::serde_json::from_slice::<U128String>(&data).ok()
+                }
+                _ => None,
+            };
+            let mut retrieved: u128 = 0;
+            match unstaked_balance {
+                Some(real_unstaked_balance) => {
+                    //WARN: This is a callback after-cross-contract-call method
+                    //busy locks must be saved false in the state, this method SHOULD NOT PANIC
+                    if self.internal_end_pending_op(sp_inx, &PendingOp::Withdraw { amount }, lock_since, false) {
+                        let withdraw_succeeded = real_unstaked_balance.0 + amount / 2 <= amount;
+                        retrieved = self.internal_apply_withdraw_result(sp_inx, amount, withdraw_succeeded);
+                        // the pool is still ours (no other op in flight), sync the rounding differences
+                        self.internal_sync_sp_unstaked(sp_inx, real_unstaked_balance.0);
+                    }
+                }
+                None => {
+                    // we can't tell if the withdraw succeeded, resync_pool will settle it
+                    let sp = &mut self.staking_pools[sp_inx];
+                    if sp.pending_op == (PendingOp::Withdraw { amount })
+                        && sp.pending_op_since == lock_since
+                        && sp.unlock(lock_since)
+                    {
+                        sp.needs_resync = true;
+                    }
+                    log!("could not get the unstaked balance from @{}", sp.account_id);
+                }
+            }
+            let sp = &self.staking_pools[sp_inx];
+            report.push(RetrieveReportJSON {
+                inx: *inx,
+                account_id: sp.account_id.clone(),
+                retrieved: retrieved.into(),
+                failed: retrieved == 0,
+            });
+            if retrieved > 0 {
+                retrieved_count += 1;
+            }
+        }
+        self.internal_pay_keeper_bounty(&keeper_id, "retrieve_all_matured", retrieved_count);
+        return report;
     }
 
     // Operator method, but open to anyone
@@ -878,6 +1822,7 @@ impl MetaPool {
 
         // Note: epoch_last_clearing is not being used right now
         self.epoch_last_clearing = env::epoch_height();
-        event!(r#"{{"event":"clr.ord","keep":"{}"}}"#, to_keep);
+        event!("epoch_clearing", { "keep": to_keep.to_string() });
+        self.internal_pay_keeper_bounty(&env::predecessor_account_id(), "end_of_epoch_clearing", 1);
     }
 }
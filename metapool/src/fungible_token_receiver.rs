@@ -0,0 +1,320 @@
+//! NEP-141 receiver: other tokens sent to this contract with ft_transfer_call
+//! msg `{"action":"stake"}` + wNEAR => unwrap and stake, the sender receives stNEAR
+//! msg `{"action":"liquid_unstake","min_expected_near":..}` + stNEAR => liquid unstake, the sender (or beneficiary_id) receives NEAR (or wNEAR)
+use crate::*;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::serde::Deserialize;
+use near_sdk::serde_json;
+use near_sdk::PromiseOrValue;
+
+#[ext_contract(ext_wnear)]
+pub trait ExtWrappedNear {
+    fn near_withdraw(&mut self, amount: U128String);
+
+    fn near_deposit(&mut self);
+
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128String, memo: Option<String>);
+}
+
+/// msg argument of ft_transfer_call
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FtTransferCallAction {
+    /// wNEAR only: unwrap and stake for the sender
+    Stake,
+    /// stNEAR only: sell to the NSLP, send the NEAR to beneficiary_id (default: the sender)
+    /// if wnear=true, the NEAR is wrapped and sent as wNEAR (beneficiary must be registered in the wNEAR contract)
+    LiquidUnstake {
+        min_expected_near: U128String,
+        beneficiary_id: Option<AccountId>,
+        wnear: Option<bool>,
+    },
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for MetaPool {
+    /// ft_transfer_call callback
+    /// returns the unused amount, the token contract refunds it to the sender
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let action: FtTransferCallAction =
+            serde_json::from_str(&msg).expect("invalid msg, expected {\"action\":...}");
+        let token_contract_id = env::predecessor_account_id();
+
+        match action {
+            FtTransferCallAction::Stake => {
+                assert!(
+                    self.wnear_contract_id.as_ref() == Some(&token_contract_id),
+                    "only wNEAR can be staked via ft_transfer_call"
+                );
+                //check what would make the stake fail before unwrapping, so ft_resolve_transfer refunds the wNEAR
+                self.assert_not_busy();
+                self.assert_min_deposit_amount(amount.0);
+                assert!(
+                    self.stake_shares_from_amount(amount.0) > 0,
+                    "amount too low to get stNEAR"
+                );
+                assert!(
+                    env::prepaid_gas()
+                        >= gas::wnear::NEAR_WITHDRAW
+                            + gas::owner_callbacks::ON_WNEAR_UNWRAPPED_FOR_STAKE
+                            + gas::BASE_GAS,
+                    "not enough gas to unwrap and stake"
+                );
+                //unwrap, the wNEAR contract transfers the NEAR to us, then stake for the sender
+                //Note: near_withdraw requires 1 yocto attached
+                ext_wnear::near_withdraw(
+                    amount.into(),
+                    &token_contract_id,
+                    1,
+                    gas::wnear::NEAR_WITHDRAW,
+                )
+                .then(ext_self_owner::on_wnear_unwrapped_for_stake(
+                    sender_id.into(),
+                    amount.into(),
+                    &env::current_account_id(),
+                    NO_DEPOSIT,
+                    gas::owner_callbacks::ON_WNEAR_UNWRAPPED_FOR_STAKE,
+                ))
+                .into()
+            }
+
+            FtTransferCallAction::LiquidUnstake {
+                min_expected_near,
+                beneficiary_id,
+                wnear,
+            } => {
+                //stNEAR is this contract, the stNEAR was already transferred to our own account
+                assert!(
+                    token_contract_id == env::current_account_id(),
+                    "only stNEAR can be liquid-unstaked"
+                );
+                self.assert_not_busy();
+                let sender_id: AccountId = sender_id.into();
+                //these accounts receive the swap fee cuts, same as internal_liquid_unstake
+                assert!(sender_id != self.treasury_account_id, "can't use treasury account");
+                assert!(sender_id != self.operator_account_id, "can't use operator account");
+                assert!(sender_id != DEVELOPERS_ACCOUNT_ID, "can't use developers account");
+                let beneficiary_id = beneficiary_id.unwrap_or(sender_id.clone());
+                assert!(
+                    env::is_valid_account_id(beneficiary_id.as_bytes()),
+                    "invalid beneficiary_id"
+                );
+                let as_wnear = wnear.unwrap_or(false);
+                let wnear_contract_id = if as_wnear {
+                    assert!(
+                        env::prepaid_gas()
+                            >= gas::wnear::NEAR_DEPOSIT
+                                + gas::owner_callbacks::ON_WNEAR_DEPOSITED_FOR_TRANSFER
+                                + gas::BASE_GAS,
+                        "not enough gas to wrap and transfer"
+                    );
+                    self.wnear_contract_id.clone().expect("wNEAR contract is not set")
+                } else {
+                    String::from("")
+                };
+
+                //sell exactly the amount received from our own account (no rounding to our whole balance)
+                //if it panics, the stNEAR is returned to the sender by ft_resolve_transfer
+                let contract_id = env::current_account_id();
+                let result =
+                    self.internal_liquid_unstake(&contract_id, amount.0, min_expected_near.0, false);
+
+                //the $META rewarded for the sale belongs to the sender
+                if result.meta.0 > 0 {
+                    let mut contract_acc = self.internal_get_account(&contract_id);
+                    contract_acc.realized_meta -= result.meta.0;
+                    self.internal_update_account(&contract_id, &contract_acc);
+                    let mut sender_acc = self.internal_get_account(&sender_id);
+                    sender_acc.realized_meta += result.meta.0;
+                    self.internal_update_account(&sender_id, &sender_acc);
+                }
+
+                if as_wnear {
+                    //wrap, then send the wNEAR in the callback
+                    self.contract_account_balance -= result.near.0;
+                    ext_wnear::near_deposit(&wnear_contract_id, result.near.0, gas::wnear::NEAR_DEPOSIT)
+                        .then(ext_self_owner::on_wnear_deposited_for_transfer(
+                            beneficiary_id,
+                            result.near,
+                            wnear_contract_id,
+                            &env::current_account_id(),
+                            NO_DEPOSIT,
+                            gas::owner_callbacks::ON_WNEAR_DEPOSITED_FOR_TRANSFER,
+                        ));
+                } else {
+                    self.native_transfer_to(&beneficiary_id, result.near.0);
+                }
+
+                //all the stNEAR was used
+                PromiseOrValue::Value(U128::from(0))
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    //prev fn: ft_on_transfer, action stake
+    /// returns the unused wNEAR amount: 0 if staked, all if the unwrap failed
+    /// the NEAR is already in the contract, so this fn must not panic
+    pub fn on_wnear_unwrapped_for_stake(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128String,
+    ) -> PromiseOrValue<U128String> {
+        assert_callback_calling();
+
+        if !is_promise_success() {
+            log!("wNEAR unwrap failed, returning {} wNEAR to {}", amount.0, sender_id);
+            //we still hold the wNEAR, the wNEAR contract refunds the sender in ft_resolve_transfer
+            return PromiseOrValue::Value(amount);
+        }
+
+        //checked by ft_on_transfer, but the contract could have been locked meanwhile
+        if self.contract_busy || self.stake_shares_from_amount(amount.0) == 0 {
+            if let Some(wnear_contract_id) = self.wnear_contract_id.clone() {
+                //wrap the NEAR again, so ft_resolve_transfer refunds the wNEAR to the sender
+                log!("can't stake now, wrapping {} NEAR again for @{}", amount.0, sender_id);
+                return ext_wnear::near_deposit(&wnear_contract_id, amount.0, gas::wnear::NEAR_DEPOSIT)
+                    .then(ext_self_owner::on_wnear_rewrapped_for_refund(
+                        sender_id,
+                        amount,
+                        &env::current_account_id(),
+                        NO_DEPOSIT,
+                        gas::owner_callbacks::ON_WNEAR_REWRAPPED_FOR_REFUND,
+                    ))
+                    .into();
+            }
+            self.internal_deposit_near_into(&sender_id, amount.0);
+            event!("wnear_stake_failed", {
+                "account_id": sender_id,
+                "amount": amount.0.to_string(),
+            });
+            return PromiseOrValue::Value(U128String::from(0));
+        }
+
+        //the NEAR was transferred to us, deposit into the sender account & stake
+        self.internal_deposit_near_into(&sender_id, amount.0);
+        self.internal_stake_from_account(&sender_id, amount.0);
+
+        //all the wNEAR was used
+        return PromiseOrValue::Value(U128String::from(0));
+    }
+
+    //prev fn: on_wnear_unwrapped_for_stake, the stake was not possible
+    /// returns the wNEAR to refund: all if the NEAR was wrapped again, else 0
+    /// and the NEAR is credited to the sender's available balance (withdraw or stake later)
+    pub fn on_wnear_rewrapped_for_refund(&mut self, sender_id: AccountId, amount: U128String) -> U128String {
+        assert_callback_calling();
+
+        if is_promise_success() {
+            //we hold the wNEAR again, the wNEAR contract refunds the sender in ft_resolve_transfer
+            return amount;
+        }
+
+        log!("can't stake now, {} NEAR left in @{}'s available balance", amount.0, sender_id);
+        event!("wnear_stake_failed", {
+            "account_id": sender_id,
+            "amount": amount.0.to_string(),
+        });
+        self.internal_deposit_near_into(&sender_id, amount.0);
+        return U128String::from(0);
+    }
+
+    //prev fn: ft_on_transfer, action liquid_unstake with wnear=true
+    /// the NEAR was wrapped, send the wNEAR to the beneficiary
+    /// if the wrap failed, the NEAR is back in the contract: credit it to the beneficiary's available balance
+    pub fn on_wnear_deposited_for_transfer(
+        &mut self,
+        beneficiary_id: AccountId,
+        amount: U128String,
+        wnear_contract_id: AccountId,
+    ) -> PromiseOrValue<()> {
+        assert_callback_calling();
+
+        if !is_promise_success() {
+            log!("wNEAR wrap failed, {} NEAR credited to @{}'s available balance", amount.0, beneficiary_id);
+            event!("wnear_transfer_failed", {
+                "account_id": beneficiary_id,
+                "amount": amount.0.to_string(),
+            });
+            self.internal_deposit_near_into(&beneficiary_id, amount.0);
+            return PromiseOrValue::Value(());
+        }
+
+        //Note: ft_transfer requires 1 yocto attached
+        ext_wnear::ft_transfer(
+            beneficiary_id.clone(),
+            amount,
+            None,
+            &wnear_contract_id,
+            1,
+            gas::wnear::FT_TRANSFER,
+        )
+        .then(ext_self_owner::on_wnear_transfer_resolved(
+            beneficiary_id,
+            amount,
+            wnear_contract_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            gas::owner_callbacks::ON_WNEAR_TRANSFER_RESOLVED,
+        ))
+        .into()
+    }
+
+    //prev fn: on_wnear_deposited_for_transfer
+    /// if the wNEAR transfer failed (e.g. beneficiary not registered in the wNEAR contract), we still hold the wNEAR:
+    /// unwrap it and credit the NEAR to the beneficiary's available balance
+    pub fn on_wnear_transfer_resolved(
+        &mut self,
+        beneficiary_id: AccountId,
+        amount: U128String,
+        wnear_contract_id: AccountId,
+    ) -> PromiseOrValue<()> {
+        assert_callback_calling();
+
+        if is_promise_success() {
+            return PromiseOrValue::Value(());
+        }
+
+        log!("wNEAR transfer to @{} failed, unwrapping {}", beneficiary_id, amount.0);
+        //Note: near_withdraw requires 1 yocto attached
+        ext_wnear::near_withdraw(amount, &wnear_contract_id, 1, gas::wnear::NEAR_WITHDRAW)
+            .then(ext_self_owner::on_wnear_unwrapped_for_refund(
+                beneficiary_id,
+                amount,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                gas::owner_callbacks::ON_WNEAR_UNWRAPPED_FOR_REFUND,
+            ))
+            .into()
+    }
+
+    //prev fn: on_wnear_transfer_resolved
+    /// the NEAR is back in the contract, credit it to the beneficiary's available balance
+    /// if the unwrap also failed, the wNEAR remains in the contract's wNEAR account (the event allows manual recovery)
+    pub fn on_wnear_unwrapped_for_refund(&mut self, beneficiary_id: AccountId, amount: U128String) {
+        assert_callback_calling();
+
+        if !is_promise_success() {
+            event!("wnear_transfer_stuck", {
+                "account_id": beneficiary_id,
+                "amount": amount.0.to_string(),
+            });
+            return;
+        }
+
+        event!("wnear_transfer_failed", {
+            "account_id": beneficiary_id,
+            "amount": amount.0.to_string(),
+        });
+        self.internal_deposit_near_into(&beneficiary_id, amount.0);
+    }
+}
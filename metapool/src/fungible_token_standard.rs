@@ -6,7 +6,7 @@ use near_contract_standards::fungible_token::{
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LazyOption;
-use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::json_types::{Base64VecU8, ValidAccountId, U128};
 use near_sdk::{
     env, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, PromiseOrValue, StorageUsage,
 };
@@ -40,22 +40,20 @@ const ONE_TGAS: Gas = 1_000_000_000_000;
 
 const NO_DEPOSIT: Balance = 0;
 
-fn ft_metadata_default() -> FungibleTokenMetadata {
+pub(crate) fn ft_metadata_default() -> FungibleTokenMetadata {
     FungibleTokenMetadata {
         spec: FT_METADATA_SPEC.to_string(),
         name: "Staked NEAR".to_string(),
         symbol: "STNEAR".to_string(),
         icon: Some(r#"data:image/svg+xml,%3csvg width='96' height='96' viewBox='0 0 96 96' fill='none' xmlns='http://www.w3.org/2000/svg'%3e%3crect width='96' height='96' rx='48' fill='white'/%3e%3cpath fill-rule='evenodd' clip-rule='evenodd' d='M48.0006 20L41.2575 26.7431L48.0006 33.4862L54.7437 26.7431L48.0006 20ZM37.281 30.7188L30.7144 37.2853L47.9998 54.5707L65.2851 37.2853L58.7186 30.7188L47.9998 41.4376L37.281 30.7188ZM26.7384 41.261L19.9953 48.0041L47.9995 76.0083L76.0037 48.0041L69.2606 41.2611L47.9995 62.5221L26.7384 41.261Z' fill='%23231B51'/%3e%3c/svg%3e"#.into()),
-        reference: Some("https://metapool.app".into()), 
+        // NEP-148: reference & reference_hash go together, set by the owner with set_ft_metadata_reference
+        reference: None,
         reference_hash: None,
         decimals: 24,
     }
 }
-fn ft_metadata_init_lazy_container() -> LazyOption<FungibleTokenMetadata> {
-    let metadata: LazyOption<FungibleTokenMetadata>;
-    metadata = LazyOption::new(b"ftmd".to_vec(), None);
-    return metadata;
-}
+/// storage key of the previous (unguarded) ft_metadata_set, removed on migration
+pub(crate) const LEGACY_FT_METADATA_KEY: &[u8] = b"ftmd";
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -170,16 +168,37 @@ impl FungibleTokenResolver for MetaPool {
 #[near_bindgen]
 impl FungibleTokenMetadataProvider for MetaPool {
     fn ft_metadata(&self) -> FungibleTokenMetadata {
-        let metadata = ft_metadata_init_lazy_container();
         //load from storage or return default
-        return metadata.get().unwrap_or(ft_metadata_default());
+        return self.ft_metadata.get().unwrap_or(ft_metadata_default());
     }
 }
 
 #[near_bindgen]
 impl MetaPool {
-    pub fn ft_metadata_set(&self, data: FungibleTokenMetadata) {
-        let mut metadata = ft_metadata_init_lazy_container();
-        metadata.set(&data); //save into storage
+    /// owner method (DAO). Sets the stNEAR icon (data URL)
+    pub fn set_ft_metadata_icon(&mut self, icon: Option<String>) {
+        self.assert_owner_calling();
+        let mut metadata = self.ft_metadata();
+        metadata.icon = icon;
+        self.internal_set_ft_metadata(&metadata);
+    }
+
+    /// owner method (DAO). Sets the stNEAR reference (link to off-chain json) and its sha256 hash
+    /// reference & reference_hash must be both set or both null
+    pub fn set_ft_metadata_reference(
+        &mut self,
+        reference: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+    ) {
+        self.assert_owner_calling();
+        let mut metadata = self.ft_metadata();
+        metadata.reference = reference;
+        metadata.reference_hash = reference_hash;
+        self.internal_set_ft_metadata(&metadata);
+    }
+
+    fn internal_set_ft_metadata(&mut self, metadata: &FungibleTokenMetadata) {
+        metadata.assert_valid();
+        self.ft_metadata.set(metadata); //save into storage
     }
 }
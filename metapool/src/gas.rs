@@ -47,6 +47,42 @@ pub mod staking_pool {
     /// staking pool.
     /// Requires BASE for local processing.
     pub const GET_ACCOUNT_TOTAL_BALANCE: u64 = super::BASE_GAS;
+
+    /// The amount of gas required to get the staked & unstaked balances of this account from the
+    /// staking pool (get_account).
+    /// Requires BASE for local processing.
+    pub const GET_ACCOUNT: u64 = super::BASE_GAS;
+
+    /// The amount of gas required to get the reward fee fraction from the staking pool.
+    /// Requires BASE for local processing.
+    pub const GET_REWARD_FEE_FRACTION: u64 = super::BASE_GAS;
+
+    /// The amount of gas required to get the current total balance of this account from the
+    /// staking pool, for each pool in distribute_rewards_batch.
+    /// Requires local processing only, smaller than BASE so a batch fits in a tx.
+    pub const GET_ACCOUNT_TOTAL_BALANCE_BATCH: u64 = 10 * super::TGAS;
+
+    /// Gas attached to withdraw_all on the staking pool contract, for each pool in retrieve_all_matured.
+    /// Requires BASE for execution & transferring the amount to us (withdraw does not re-stake).
+    pub const WITHDRAW_ALL_BATCH: u64 = super::BASE_GAS;
+
+    /// The amount of gas required to get current unstaked balance of this account from the
+    /// staking pool, for each pool in retrieve_all_matured.
+    pub const GET_ACCOUNT_UNSTAKED_BALANCE_BATCH: u64 = 10 * super::TGAS;
+}
+
+pub mod wnear {
+    /// Gas attached to near_withdraw on the wNEAR contract.
+    /// Requires BASE for execution + BASE for transferring the NEAR to us.
+    pub const NEAR_WITHDRAW: u64 = super::BASE_GAS * 2;
+
+    /// Gas attached to near_deposit on the wNEAR contract.
+    /// Requires BASE for execution.
+    pub const NEAR_DEPOSIT: u64 = super::BASE_GAS;
+
+    /// Gas attached to ft_transfer on the wNEAR contract.
+    /// Requires BASE for execution.
+    pub const FT_TRANSFER: u64 = super::BASE_GAS;
 }
 
 pub mod transfer_poll {
@@ -101,6 +137,48 @@ pub mod owner_callbacks {
     /// Gas attached to the inner callback for sync_unstaked_balance to get precise unstaked balance from the staking pool.
     pub const ON_GET_SP_UNSTAKED_BALANCE: u64 = super::BASE_GAS; // just update unstaked amount (yocto differences)
 
+    /// Gas attached to the inner callback for resync_pool, settles the op in flight from the real balances.
+    /// Requires BASE for local updates.
+    pub const ON_RESYNC_POOL: u64 = super::BASE_GAS;
+
+    /// Gas attached to the inner callback for check_pool_fee, records the fee and maybe sets the pool weight to 0
+    pub const ON_GET_SP_REWARD_FEE_FRACTION: u64 = super::BASE_GAS;
+
+    /// Gas attached to the inner callback of distribute_rewards_batch: BASE + PER_POOL for each pool queried
+    /// PER_POOL covers settling a pool with rewards: operator & developers stNEAR mint, sp performance & price history
+    /// (measured in the sim test gas_distribute_rewards_batch)
+    pub const ON_GET_SP_TOTAL_BALANCE_BATCH_BASE: u64 = super::BASE_GAS;
+    pub const ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL: u64 = 40 * super::TGAS;
+
+    /// Gas attached to the final callback of retrieve_all_matured: BASE + PER_POOL for each pool
+    pub const ON_RETRIEVE_ALL_MATURED: u64 = super::BASE_GAS;
+    /// PER_POOL covers settling the withdraw & syncing unstaked
+    pub const ON_RETRIEVE_ALL_MATURED_PER_POOL: u64 = 5 * super::TGAS;
+
+    /// Gas attached to the inner callback for processing result of near_withdraw on the wNEAR contract.
+    /// Requires BASE for staking + BASE for nslp internal clearing,
+    /// or if it can't stake: BASE for local updates + gas for near_deposit + gas for another callback.
+    pub const ON_WNEAR_UNWRAPPED_FOR_STAKE: u64 =
+        super::BASE_GAS + super::wnear::NEAR_DEPOSIT + ON_WNEAR_REWRAPPED_FOR_REFUND;
+
+    /// Gas attached to the inner callback for processing result of near_deposit when the unwrapped wNEAR could not be staked.
+    /// Requires BASE for local updates.
+    pub const ON_WNEAR_REWRAPPED_FOR_REFUND: u64 = super::BASE_GAS;
+
+    /// Gas attached to the inner callback for processing result of near_deposit on the wNEAR contract (liquid unstake as wNEAR).
+    /// Requires BASE for local updates + gas for ft_transfer + gas for another callback.
+    pub const ON_WNEAR_DEPOSITED_FOR_TRANSFER: u64 =
+        super::BASE_GAS + super::wnear::FT_TRANSFER + ON_WNEAR_TRANSFER_RESOLVED;
+
+    /// Gas attached to the inner callback for processing result of ft_transfer on the wNEAR contract.
+    /// Requires BASE for local updates + gas for near_withdraw + gas for another callback.
+    pub const ON_WNEAR_TRANSFER_RESOLVED: u64 =
+        super::BASE_GAS + super::wnear::NEAR_WITHDRAW + ON_WNEAR_UNWRAPPED_FOR_REFUND;
+
+    /// Gas attached to the inner callback for processing result of near_withdraw after a failed wNEAR transfer.
+    /// Requires BASE for local updates.
+    pub const ON_WNEAR_UNWRAPPED_FOR_REFUND: u64 = super::BASE_GAS;
+
     /// Gas attached to the inner callback for processing result of the call to get the current
     /// unstaked balance from the staking pool.
     /// The callback might proceed with withdrawing this amount.
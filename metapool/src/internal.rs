@@ -27,10 +27,69 @@ impl MetaPool {
         );
     }
 
-    pub fn assert_not_busy(&self) {
+    /// account_id matches an allowed factory suffix (".poolv1.near") or an exact allowed account
+    pub(crate) fn is_staking_pool_account_allowed(&self, account_id: &AccountId) -> bool {
+        self.staking_pool_allowed_accounts.iter().any(|item| {
+            account_id == item || (item.starts_with('.') && account_id.ends_with(item.as_str()))
+        })
+    }
+
+    pub(crate) fn is_staking_pool_in_list(&self, account_id: &AccountId) -> bool {
+        self.staking_pools
+            .iter()
+            .any(|sp| &sp.account_id == account_id)
+    }
+
+    /// an expired contract lock (its callback never ran) does not block: the stale locks are released here
+    pub fn assert_not_busy(&mut self) {
+        if self.contract_busy && self.contract_lock_expired() {
+            self.internal_clear_stale_locks();
+        }
         assert!(!self.contract_busy, "Contract is busy. Try again later");
     }
 
+    pub(crate) fn contract_lock_expired(&self) -> bool {
+        return env::block_index() >= self.contract_busy_since + self.busy_lock_expiry_blocks;
+    }
+
+    /// releases the contract lock taken at lock_since, unless it expired and was released (maybe taken again) meanwhile
+    pub(crate) fn internal_release_contract_lock(&mut self, lock_since: BlockHeight) {
+        if self.contract_busy && self.contract_busy_since == lock_since {
+            self.contract_busy = false;
+        }
+    }
+
+    /// releases the contract & pool locks taken more than busy_lock_expiry_blocks ago.
+    /// A pool released with an op in flight is marked needs_resync. Returns how many locks were released
+    pub(crate) fn internal_clear_stale_locks(&mut self) -> u16 {
+        let mut cleared: u16 = 0;
+        if self.contract_busy && self.contract_lock_expired() {
+            self.contract_busy = false;
+            cleared += 1;
+            event!("lock_cleared", {
+                "lock": "contract",
+                "since": self.contract_busy_since,
+            });
+        }
+        let expiry_blocks = self.busy_lock_expiry_blocks;
+        for sp in self.staking_pools.iter_mut() {
+            if sp.busy_lock && sp.lock_expired(expiry_blocks) {
+                sp.busy_lock = false;
+                if !sp.pending_op.is_none() {
+                    sp.needs_resync = true;
+                }
+                cleared += 1;
+                event!("lock_cleared", {
+                    "lock": "sp",
+                    "sp": sp.account_id,
+                    "since": sp.busy_lock_since,
+                    "needs_resync": sp.needs_resync,
+                });
+            }
+        }
+        return cleared;
+    }
+
     pub fn assert_min_deposit_amount(&self, amount: u128) {
         assert!(
             amount >= self.min_deposit_amount,
@@ -50,8 +109,11 @@ impl MetaPool {
     }
 
     pub(crate) fn internal_deposit_attached_near_into(&mut self, account_id: AccountId) {
-        let amount = env::attached_deposit();
+        self.internal_deposit_near_into(&account_id, env::attached_deposit());
+    }
 
+    /// the NEAR is already in the contract account (attached or received), add it to the account available balance
+    pub(crate) fn internal_deposit_near_into(&mut self, account_id: &AccountId, amount: u128) {
         let mut account = self.internal_get_account(&account_id);
 
         account.available += amount;
@@ -84,7 +146,9 @@ impl MetaPool {
 
         if from_unstaked {
             //MIMIC staking-pool, move 1st form unstaked->available, it must be free to withdraw
-            account.in_memory_try_finish_unstaking(&account_id, requested_amount, self);
+            let mut claims = self.internal_get_unstake_claims(&account_id, &account);
+            account.in_memory_try_finish_unstaking(&account_id, requested_amount, &mut claims, self);
+            self.internal_save_unstake_claims(&account_id, &claims);
         }
 
         let amount = account.take_from_available(requested_amount, self);
@@ -96,13 +160,61 @@ impl MetaPool {
         //     self.min_account_balance/NEAR);
 
         self.internal_update_account(&account_id, &account);
+
+        event!("withdraw", {
+            "account_id": account_id,
+            "amount": amount.to_string(),
+            "from_unstaked": from_unstaked,
+        });
+
         //transfer to user native near account
         return self.native_transfer_to_predecessor(amount);
     }
     pub(crate) fn native_transfer_to_predecessor(&mut self, amount: u128) -> Promise {
+        self.native_transfer_to(&env::predecessor_account_id(), amount)
+    }
+
+    pub(crate) fn native_transfer_to(&mut self, account_id: &AccountId, amount: u128) -> Promise {
         //transfer to user native near account
         self.contract_account_balance -= amount;
-        return Promise::new(env::predecessor_account_id()).transfer(amount);
+        return Promise::new(account_id.clone()).transfer(amount);
+    }
+
+    /// pays keeper_bounty x units to the caller of a heartbeat fn (keeper_id) for the work done.
+    /// Async work is paid from the callbacks, only if it succeeded.
+    /// taken from the extra balance (extra_balance_accumulated), at most keeper_bounty_max_per_epoch per epoch
+    pub(crate) fn internal_pay_keeper_bounty(&mut self, keeper_id: &AccountId, action: &str, units: u16) {
+        if self.keeper_bounty == 0 || units == 0 {
+            return;
+        }
+        let epoch = env::epoch_height();
+        if self.keeper_bounty_epoch != epoch {
+            self.keeper_bounty_epoch = epoch;
+            self.keeper_bounty_paid_this_epoch = 0;
+        }
+        // keep 10 cents of the extra balance for transfer fees
+        let extra_balance = env::account_balance()
+            .saturating_sub(self.contract_account_balance)
+            .saturating_sub(10 * NEAR_CENT);
+        let amount = std::cmp::min(
+            std::cmp::min(
+                self.keeper_bounty * units as u128,
+                self.keeper_bounty_max_per_epoch
+                    .saturating_sub(self.keeper_bounty_paid_this_epoch),
+            ),
+            extra_balance,
+        );
+        if amount == 0 {
+            return;
+        }
+        self.keeper_bounty_paid_this_epoch += amount;
+        //not from contract_account_balance, the extra balance is not accounted
+        Promise::new(keeper_id.clone()).transfer(amount);
+        event!("keeper_bounty", {
+            "account_id": keeper_id,
+            "action": action,
+            "amount": amount.to_string(),
+        });
     }
 
     //------------------------------
@@ -113,7 +225,13 @@ impl MetaPool {
 
         self.assert_min_deposit_amount(user_amount);
 
-        let account_id = env::predecessor_account_id();
+        self.internal_stake_from_account(&env::predecessor_account_id(), user_amount);
+    }
+
+    /// takes from account_id available and mints stNEAR for account_id
+    /// callers must check the contract is not busy & the min deposit amount
+    pub(crate) fn internal_stake_from_account(&mut self, account_id: &AccountId, user_amount: Balance) {
+        let account_id = account_id.clone();
         let mut acc = self.internal_get_account(&account_id);
 
         //take from the account "available" balance
@@ -136,20 +254,214 @@ impl MetaPool {
         self.internal_update_account(&account_id, &acc);
 
         //log event
-        event!(
-            r#"{{"event":"STAKE","account":"{}","amount":"{}"}}"#,
-            account_id,
-            amount
-        );
+        event!("stake", {
+            "account_id": account_id,
+            "amount": amount.to_string(),
+            "shares": num_shares.to_string(),
+        });
+        log_ft_mint(&account_id, num_shares);
 
         //----------
         //check if the liquidity pool needs liquidity, and then use this opportunity to liquidate stnear in the LP by internal-clearing
         self.nslp_try_internal_clearing();
     }
 
+    //------------------------------
+    /// sells account_id's stNEAR to the NSLP (liquid unstake)
+    /// the NEAR received is taken from the account available, the caller must transfer it (result.near)
+    /// allow_rounding: sell the whole balance if st_near_to_burn is close to it. false to sell exactly st_near_to_burn
+    pub(crate) fn internal_liquid_unstake(
+        &mut self,
+        account_id: &AccountId,
+        st_near_to_burn: u128,
+        min_expected_near: u128,
+        allow_rounding: bool,
+    ) -> LiquidUnstakeResult {
+        let account_id = account_id.clone();
+        let mut user_account = self.internal_get_account(&account_id);
+
+        let stnear_owned = user_account.stake_shares;
+
+        let st_near_to_sell:u128 =
+        // if the amount is close to user's total, remove user's total
+        // to: a) do not leave less than ONE_MILLI_NEAR in the account, b) Allow 10 yoctos of rounding, e.g. remove(100) removes 99.999993 without panicking
+        if allow_rounding && is_close(st_near_to_burn, stnear_owned) { // allow for rounding simplification
+            stnear_owned
+        }
+        else  {
+            st_near_to_burn
+        };
+
+        debug!(
+            "st_near owned:{}, to_sell:{}",
+            user_account.stake_shares, st_near_to_sell
+        );
+
+        assert!(
+            stnear_owned >= st_near_to_sell,
+            "Not enough stNEAR. You own {}",
+            stnear_owned
+        );
+
+        let mut nslp_account = self.internal_get_nslp_account();
+
+        //compute how many nears are the st_near valued at
+        let nears_out = self.amount_from_stake_shares(st_near_to_sell);
+        let swap_fee_basis_points =
+            self.internal_get_discount_basis_points(nslp_account.available, nears_out);
+        assert!(swap_fee_basis_points < 10000, "inconsistency d>1");
+        let fee = apply_pct(swap_fee_basis_points, nears_out);
+
+        let near_to_receive = nears_out - fee;
+        assert!(
+            near_to_receive >= min_expected_near,
+            "Price changed, your min amount {} is not satisfied {}. Try again",
+            min_expected_near,
+            near_to_receive
+        );
+        assert!(
+            nslp_account.available >= near_to_receive,
+            "Not enough liquidity in the liquidity pool"
+        );
+
+        //the NEAR for the user comes from the LP
+        nslp_account.available -= near_to_receive;
+        user_account.available += near_to_receive;
+
+        // keep track of meta rewards for LPs
+        self.est_meta_rewards_lp += damp_multiplier(
+            fee,
+            self.lp_provider_meta_mult_pct,
+            self.est_meta_rewards_lp,
+            self.max_meta_rewards_lp,
+        );
+
+        // compute how many shares the swap fee represent
+        let fee_in_st_near = self.stake_shares_from_amount(fee);
+
+        // involved accounts
+        assert!(
+            &account_id != &self.treasury_account_id,
+            "can't use treasury account"
+        );
+        let mut treasury_account = self.internal_get_account(&self.treasury_account_id);
+        assert!(
+            &account_id != &self.operator_account_id,
+            "can't use operator account"
+        );
+        let mut operator_account = self.internal_get_account(&self.operator_account_id);
+        assert!(
+            &account_id != &DEVELOPERS_ACCOUNT_ID,
+            "can't use developers account"
+        );
+        let mut developers_account = self.internal_get_account(&DEVELOPERS_ACCOUNT_ID.into());
+
+        // The treasury cut in stnear-shares (25% by default)
+        let treasury_st_near_cut = apply_pct(self.treasury_swap_cut_basis_points, fee_in_st_near);
+        // part of the treasury cut goes to the insurance fund
+        let insurance_st_near_cut =
+            apply_pct(self.insurance_swap_cut_basis_points, treasury_st_near_cut);
+        treasury_account.add_st_near(treasury_st_near_cut - insurance_st_near_cut, &self);
+        let mut insurance_account = self.internal_get_account(&INSURANCE_INTERNAL_ACCOUNT.into());
+        insurance_account.add_st_near(insurance_st_near_cut, &self);
+
+        // The cut that the contract owner (operator) takes. (3% of 1% normally)
+        let operator_st_near_cut = apply_pct(self.operator_swap_cut_basis_points, fee_in_st_near);
+        operator_account.add_st_near(operator_st_near_cut, &self);
+
+        // The cut that the developers take. (2% of 1% normally)
+        let developers_st_near_cut = apply_pct(DEVELOPERS_SWAP_CUT_BASIS_POINTS, fee_in_st_near);
+        developers_account.add_st_near(developers_st_near_cut, &self);
+
+        // all the realized meta from non-liq.provider cuts (30%), send to operator & developers
+        let st_near_non_lp_cut =
+            treasury_st_near_cut + operator_st_near_cut + developers_st_near_cut;
+        let meta_from_operation = damp_multiplier(
+            st_near_non_lp_cut,
+            self.lp_provider_meta_mult_pct,
+            self.est_meta_rewards_lp,
+            self.max_meta_rewards_lp,
+        );
+        self.total_meta += meta_from_operation;
+        operator_account.realized_meta += meta_from_operation / 2;
+        developers_account.realized_meta += meta_from_operation / 2;
+
+        debug!("treasury_st_near_cut:{} operator_st_near_cut:{} developers_st_near_cut:{} fee_in_st_near:{}",
+            treasury_st_near_cut,operator_st_near_cut,developers_st_near_cut,fee_in_st_near);
+
+        assert!(
+            fee_in_st_near > treasury_st_near_cut + developers_st_near_cut + operator_st_near_cut
+        );
+
+        // The rest of the st_near sold goes into the liq-pool. Because it is a larger amount than NEARs removed, it will increase share value for all LP providers.
+        // Adding value to the pool via adding more stNEAR value than the NEAR removed, will be counted as rewards for the nslp_meter,
+        // so $META for LP providers will be created. $METAs for LP providers are realized during add_liquidity(), remove_liquidity()
+        let st_near_to_liq_pool = st_near_to_sell
+            - (treasury_st_near_cut + operator_st_near_cut + developers_st_near_cut);
+        debug!("nslp_account.add_st_near {}", st_near_to_liq_pool);
+        // major part of stNEAR sold goes to the NSLP
+        nslp_account.add_st_near(st_near_to_liq_pool, &self);
+
+        //complete the transfer, remove stnear from the user (stnear was transferred to the LP & others)
+        user_account.sub_st_near(st_near_to_sell, &self);
+        //mint $META for the selling user
+        let meta_to_seller = damp_multiplier(
+            fee_in_st_near,
+            self.stnear_sell_meta_mult_pct,
+            self.est_meta_rewards_lu,
+            self.max_meta_rewards_lu,
+        );
+        self.total_meta += meta_to_seller;
+        // keep track of meta rewards for lu's
+        self.est_meta_rewards_lu += meta_to_seller;
+        user_account.realized_meta += meta_to_seller;
+
+        //Save involved accounts
+        self.internal_update_account(&self.treasury_account_id.clone(), &treasury_account);
+        self.internal_update_account(&INSURANCE_INTERNAL_ACCOUNT.into(), &insurance_account);
+        self.internal_update_account(&self.operator_account_id.clone(), &operator_account);
+        self.internal_update_account(&DEVELOPERS_ACCOUNT_ID.into(), &developers_account);
+        //Save nslp accounts
+        self.internal_save_nslp_account(&nslp_account);
+
+        //take from available, the caller transfers the NEAR
+        let transfer_amount = user_account.take_from_available(near_to_receive, self);
+
+        //Save user account
+        self.internal_update_account(&account_id, &user_account);
+
+        log!(
+            "@{} liquid-unstaked {} stNEAR, got {} NEAR and {} $META",
+            &account_id,
+            st_near_to_sell,
+            transfer_amount,
+            meta_to_seller
+        );
+        event!("liquid_unstake", {
+            "account_id": account_id,
+            "stnear": st_near_to_sell.to_string(),
+            "near": transfer_amount.to_string(),
+            "fee": fee_in_st_near.to_string(),
+            "meta": meta_to_seller.to_string(),
+        });
+        log_ft_transfer(&account_id, NSLP_INTERNAL_ACCOUNT, st_near_to_liq_pool);
+        log_ft_transfer(&account_id, &self.treasury_account_id, treasury_st_near_cut - insurance_st_near_cut);
+        log_ft_transfer(&account_id, INSURANCE_INTERNAL_ACCOUNT, insurance_st_near_cut);
+        log_ft_transfer(&account_id, &self.operator_account_id, operator_st_near_cut);
+        log_ft_transfer(&account_id, DEVELOPERS_ACCOUNT_ID, developers_st_near_cut);
+
+        return LiquidUnstakeResult {
+            near: transfer_amount.into(),
+            fee: fee_in_st_near.into(),
+            meta: meta_to_seller.into(),
+        };
+    }
+
     //------------------------------
     /// amount_requested is in NEAR
-    pub(crate) fn internal_unstake(&mut self, amount_requested: u128) {
+    /// if as_ticket, the claim is minted as a NEP-171 unstake ticket owned by the account
+    /// returns (amount unstaked, unlock epoch)
+    pub(crate) fn internal_unstake(&mut self, amount_requested: u128, as_ticket: bool) -> (u128, EpochHeight) {
         self.assert_not_busy();
 
         let account_id = env::predecessor_account_id();
@@ -184,10 +496,22 @@ impl MetaPool {
         //remove acc stake shares
         acc.sub_stake_shares(stake_shares_to_burn, amount_to_unstake);
         //the amount is now "unstaked", i.e. the user has a claim to this amount, 4-8 epochs form now
-        acc.unstaked += amount_to_unstake;
-        acc.unstaked_requested_unlock_epoch =
+        let unlock_epoch =
             env::epoch_height() + self.internal_compute_current_unstaking_delay(amount_to_unstake); //when the unstake will be available
-                                                                                                    //--contract totals
+        self.internal_track_unstake_claim(unlock_epoch, amount_to_unstake);
+        let mut claims = self.internal_get_unstake_claims(&account_id, &acc);
+        let mut ticket_id: TokenId = String::new();
+        if as_ticket {
+            // the claim goes into a transferable ticket, not into acc.unstaked
+            ticket_id = self.internal_mint_unstake_ticket(&account_id, amount_to_unstake, unlock_epoch);
+        } else {
+            add_unstake_claim(&mut claims, amount_to_unstake, unlock_epoch);
+            acc.unstaked += amount_to_unstake;
+            // the account unlock epoch is when *all* the claims are available (staking-pool trait semantics)
+            acc.unstaked_requested_unlock_epoch =
+                std::cmp::max(acc.unstaked_requested_unlock_epoch, unlock_epoch);
+        }
+        //--contract totals
         self.epoch_unstake_orders += amount_to_unstake;
         self.total_unstake_claims += amount_to_unstake;
         self.total_stake_shares -= stake_shares_to_burn; //burn
@@ -195,13 +519,16 @@ impl MetaPool {
 
         //--SAVE ACCOUNT--
         self.internal_update_account(&account_id, &acc);
+        self.internal_save_unstake_claims(&account_id, &claims);
 
-        event!(
-            r#"{{"event":"D-UNSTK","account_id":"{}","amount":"{}","shares":"{}"}}"#,
-            account_id,
-            amount_to_unstake,
-            stake_shares_to_burn
-        );
+        event!("unstake", {
+            "account_id": account_id,
+            "amount": amount_to_unstake.to_string(),
+            "shares": stake_shares_to_burn.to_string(),
+            "unlock_epoch": unlock_epoch.to_string(),
+            "ticket": if as_ticket { Some(&ticket_id) } else { None },
+        });
+        log_ft_burn(&account_id, stake_shares_to_burn);
 
         log!(
             "@{} unstaked {}. Has now {} unstaked and {} stNEAR. Epoch:{}",
@@ -211,11 +538,13 @@ impl MetaPool {
             acc.stake_shares,
             env::epoch_height()
         );
+
+        return (amount_to_unstake, unlock_epoch);
     }
 
     //--------------------------------------------------
     /// adds liquidity from deposited amount
-    pub(crate) fn internal_nslp_add_liquidity(&mut self, amount_requested: u128) -> u16 {
+    pub(crate) fn internal_nslp_add_liquidity(&mut self, amount_requested: u128, min_shares: u128) -> u16 {
         self.assert_not_busy();
 
         let account_id = env::predecessor_account_id();
@@ -233,6 +562,12 @@ impl MetaPool {
         // Calculate the number of "nslp" shares the account will receive for adding the given amount of near liquidity
         let num_shares = self.nslp_shares_from_amount(amount, &nslp_account);
         assert!(num_shares > 0);
+        assert!(
+            num_shares >= min_shares,
+            "Price changed, your min shares {} is not satisfied {}. Try again",
+            min_shares,
+            num_shares
+        );
 
         //register added liquidity to compute rewards correctly
         acc.lp_meter.stake(amount);
@@ -251,11 +586,11 @@ impl MetaPool {
         self.internal_update_account(&account_id, &acc);
         self.internal_save_nslp_account(&nslp_account);
 
-        event!(
-            r#"{{"event":"ADD.L","account_id":"{}","amount":"{}"}}"#,
-            account_id,
-            amount
-        );
+        event!("add_liquidity", {
+            "account_id": account_id,
+            "amount": amount.to_string(),
+            "shares": num_shares.to_string(),
+        });
 
         return result_bp;
     }
@@ -268,7 +603,7 @@ impl MetaPool {
         for (_, sp) in self.staking_pools.iter().enumerate() {
             //if the pool has no unstaking in process
             total_staked += sp.staked;
-            if !sp.busy_lock && sp.staked > 0 && sp.wait_period_ended() {
+            if !sp.is_busy() && sp.staked > 0 && sp.wait_period_ended() {
                 normal_wait_staked_available += sp.staked;
                 if normal_wait_staked_available > amount {
                     return NUM_EPOCHS_TO_UNLOCK;
@@ -291,6 +626,12 @@ impl MetaPool {
             self.internal_update_account(&account_id, &account);
             // Increasing the total amount of stake shares (reduces price)
             self.total_stake_shares += num_shares;
+
+            event!("fee_mint", {
+                "account_id": account_id,
+                "shares": num_shares.to_string(),
+            });
+            log_ft_mint(&account_id, num_shares);
         }
     }
 
@@ -370,11 +711,11 @@ impl MetaPool {
 
             log!("NSLP clearing {} {}", st_near_to_sell, near_value);
             //log event
-            event!(
-                r#"{{"event":"NSLP.clr","shares":"{}","amount":"{}"}}"#,
-                st_near_to_sell,
-                near_value
-            );
+            event!("nslp_clearing", {
+                "shares": st_near_to_sell.to_string(),
+                "amount": near_value.to_string(),
+            });
+            log_ft_burn(NSLP_INTERNAL_ACCOUNT, st_near_to_sell);
 
             //users made a deposit+mint, and now we need to convert that into a 0-fee swap NEAR<->stNEAR
             //we take NEAR from the contract, but let the users keep their minted stNEAR
@@ -425,6 +766,27 @@ impl MetaPool {
         return self.nslp_max_discount_basis_points - proportional_bp as u16;
     }
 
+    /// max NEAR that can be taken from the NSLP with a swap fee <= max_fee_bp
+    pub(crate) fn internal_max_nears_for_fee(&self, available_near: u128, max_fee_bp: u16) -> u128 {
+        if max_fee_bp >= self.nslp_max_discount_basis_points {
+            return available_near;
+        }
+        if max_fee_bp < self.nslp_min_discount_basis_points {
+            return 0;
+        }
+        //linear curve from max to min on target, fee <= max_fee_bp when near_after >= min_near_after
+        let range = self.nslp_max_discount_basis_points - self.nslp_min_discount_basis_points;
+        if range == 0 {
+            return available_near;
+        }
+        let min_near_after = proportional(
+            self.nslp_liquidity_target,
+            (self.nslp_max_discount_basis_points - max_fee_bp) as u128,
+            range as u128,
+        ) + 1; // round up
+        return available_near.saturating_sub(min_near_after);
+    }
+
     /// NEAR/stNEAR SWAP functions
     /// return how much NEAR you can get by selling x stNEAR
     pub(crate) fn internal_get_near_amount_sell_stnear(
@@ -464,6 +826,40 @@ impl MetaPool {
         }
     }
 
+    /// Inner method to get the pending unstake claims of an account.
+    /// An unstaked balance from before the claims-queue existed is returned as a single claim using the account's unlock epoch
+    pub(crate) fn internal_get_unstake_claims(
+        &self,
+        account_id: &String,
+        account: &Account,
+    ) -> Vec<UnstakeClaim> {
+        let mut claims = self.unstake_claims.get(account_id).unwrap_or_default();
+        let sum_claims: u128 = claims.iter().map(|claim| claim.amount).sum();
+        if account.unstaked > sum_claims {
+            claims.insert(
+                0,
+                UnstakeClaim {
+                    amount: account.unstaked - sum_claims,
+                    unlock_epoch: account.unstaked_requested_unlock_epoch,
+                },
+            );
+        }
+        return claims;
+    }
+
+    /// Inner method to save the pending unstake claims of an account. Removed when there are no claims left
+    pub(crate) fn internal_save_unstake_claims(
+        &mut self,
+        account_id: &String,
+        claims: &Vec<UnstakeClaim>,
+    ) {
+        if claims.is_empty() {
+            self.unstake_claims.remove(account_id);
+        } else {
+            self.unstake_claims.insert(account_id, claims);
+        }
+    }
+
     /// Inner method to get the given account or a new default value account.
     pub(crate) fn internal_get_nslp_account(&self) -> Account {
         self.accounts
@@ -481,8 +877,8 @@ impl MetaPool {
         let mut selected_sp_inx: usize = 0;
 
         for (sp_inx, sp) in self.staking_pools.iter().enumerate() {
-            // if the pool is not busy, and this pool can stake
-            if !sp.busy_lock && sp.weight_basis_points > 0 {
+            // if the pool is not busy, and this pool can stake (not being evacuated)
+            if !sp.is_busy() && sp.weight_basis_points > 0 && sp.evacuation_amount == 0 {
                 // if this pool has an unbalance requiring staking
                 let should_have = apply_pct(sp.weight_basis_points, self.total_for_staking);
                 // this pool requires staking?
@@ -513,6 +909,29 @@ impl MetaPool {
         return (selected_sp_inx, selected_to_stake_amount);
     }
 
+    /// finds the most over-weight pool for rebalance_unstake, amount capped at max_amount
+    /// only pools without unstaked funds waiting (the unstake would extend the wait for the user claims)
+    /// WARN: returns (0,0) if no pool requires rebalancing/all are busy
+    pub(crate) fn get_staking_pool_requiring_rebalance(&self, max_amount: u128) -> (usize, u128) {
+        let mut selected_amount: u128 = 0;
+        let mut selected_sp_inx: usize = 0;
+
+        for (sp_inx, sp) in self.staking_pools.iter().enumerate() {
+            if !sp.is_busy() && sp.unstaked == 0 && sp.evacuation_amount == 0 {
+                let should_have = apply_pct(sp.weight_basis_points, self.total_for_staking);
+                // over-weight by more than the minimum movement?
+                if sp.staked > should_have + MIN_STAKE_UNSTAKE_AMOUNT_MOVEMENT
+                    && sp.staked - should_have > selected_amount
+                {
+                    selected_amount = sp.staked - should_have;
+                    selected_sp_inx = sp_inx;
+                }
+            }
+        }
+
+        return (selected_sp_inx, std::cmp::min(selected_amount, max_amount));
+    }
+
     /// finds a staking pool requiring some stake to get balanced
     /// WARN: returns (0,0) if no pool requires staking/all are busy
     pub(crate) fn get_staking_pool_requiring_unstake(
@@ -525,7 +944,7 @@ impl MetaPool {
 
         for (sp_inx, sp) in self.staking_pools.iter().enumerate() {
             // if the pool is not busy, has stake
-            if !sp.busy_lock && sp.staked > 0 {
+            if !sp.is_busy() && sp.staked > 0 {
                 //if has not unstaked balance waiting for withdrawal, or wait started in this same epoch (no harm in unstaking more)
                 // TODO: Unstaking in the same epoch is only an issue, if you hit the last block of the epoch.
                 //    In this case the receipt may be executed at the next epoch.
@@ -589,6 +1008,8 @@ impl MetaPool {
 
         self.internal_update_account(&sender_id, &sender_acc);
         self.internal_update_account(&receiver_id, &receiver_acc);
+
+        log_ft_transfer(sender_id, receiver_id, amount);
     }
 
     // MULTI FUN TOKEN [NEP-138](https://github.com/near/NEPs/pull/138)
@@ -697,6 +1118,7 @@ impl MetaPool {
                     receiver_id,
                     sender_id
                 );
+                log_ft_transfer(&receiver_id, &sender_id, refund_amount);
                 return (amount - refund_amount, 0);
             }
         }
@@ -0,0 +1,144 @@
+//! On-chain accounting invariant checker (view), for monitoring
+//! walks accounts & unstake tickets in pages, the partial sums are passed back on each call.
+//! When the last page is reached, the sums are compared with the contract totals
+use crate::*;
+
+fn discrepancy(check: &str, expected: u128, actual: u128) -> InvariantDiscrepancyJSON {
+    return InvariantDiscrepancyJSON {
+        check: String::from(check),
+        expected: expected.into(),
+        actual: actual.into(),
+    };
+}
+
+#[near_bindgen]
+impl MetaPool {
+    /// checks the contract accounting invariants.
+    /// items are all the accounts followed by all the unstake tickets. Start with from_index=0 and sums=null,
+    /// then call again with from_index=next_index & the returned sums until complete=true
+    pub fn check_invariants(
+        &self,
+        from_index: u64,
+        limit: u64,
+        sums: Option<InvariantSumsJSON>,
+    ) -> InvariantsCheckJSON {
+        let mut sum_available: u128 = 0;
+        let mut sum_unstaked: u128 = 0;
+        let mut sum_stake_shares: u128 = 0;
+        let mut sum_unstake_tickets: u128 = 0;
+        if let Some(prev) = sums {
+            sum_available = prev.available.0;
+            sum_unstaked = prev.unstaked.0;
+            sum_stake_shares = prev.stake_shares.0;
+            sum_unstake_tickets = prev.unstake_tickets.0;
+        }
+
+        let mut discrepancies: Vec<InvariantDiscrepancyJSON> = Vec::new();
+
+        let account_keys = self.accounts.keys_as_vector();
+        let tickets = self.unstake_tickets.values_as_vector();
+        let total_items = account_keys.len() + tickets.len();
+        let next_index = std::cmp::min(from_index + limit, total_items);
+
+        for inx in from_index..next_index {
+            if inx < account_keys.len() {
+                let account_id = account_keys.get(inx).unwrap();
+                let acc = self.internal_get_account(&account_id);
+                sum_available += acc.available;
+                sum_unstaked += acc.unstaked;
+                sum_stake_shares += acc.stake_shares;
+                // acc.unstaked == sum(claims)
+                let claims = self.internal_get_unstake_claims(&account_id, &acc);
+                let sum_claims: u128 = claims.iter().map(|claim| claim.amount).sum();
+                if sum_claims != acc.unstaked {
+                    discrepancies.push(discrepancy(
+                        &format!("account.unstaked == sum(claims) @{}", account_id),
+                        sum_claims,
+                        acc.unstaked,
+                    ));
+                }
+            } else {
+                let ticket = tickets.get(inx - account_keys.len()).unwrap();
+                sum_unstake_tickets += ticket.amount;
+            }
+        }
+
+        //---- contract level checks, on every page
+        // funds being unstaked: in the pools (waiting) or not yet sent (orders)
+        // pools being evacuated have unstaked funds that are not for claims
+        let claims_funds = self.reserve_for_unstake_claims
+            + self.total_unstaked_and_waiting
+            + self.epoch_unstake_orders;
+        let evacuating: u128 = self.staking_pools.iter().map(|sp| sp.evacuation_amount).sum();
+        if self.total_unstake_claims + evacuating != claims_funds {
+            discrepancies.push(discrepancy(
+                "total_unstake_claims + sum(sp.evacuation_amount) == reserve_for_unstake_claims + total_unstaked_and_waiting + epoch_unstake_orders",
+                self.total_unstake_claims + evacuating,
+                claims_funds,
+            ));
+        }
+        // the account balance can be greater (tx-fees, storage), but never less
+        if env::account_balance() < self.contract_account_balance {
+            discrepancies.push(discrepancy(
+                "env::account_balance() >= contract_account_balance",
+                self.contract_account_balance,
+                env::account_balance(),
+            ));
+        }
+        let sum_pools_staked: u128 = self.staking_pools.iter().map(|sp| sp.staked).sum();
+        if sum_pools_staked != self.total_actually_staked {
+            discrepancies.push(discrepancy(
+                "total_actually_staked == sum(sp.staked)",
+                self.total_actually_staked,
+                sum_pools_staked,
+            ));
+        }
+        let sum_pools_unstaked: u128 = self.staking_pools.iter().map(|sp| sp.unstaked).sum();
+        if sum_pools_unstaked != self.total_unstaked_and_waiting {
+            discrepancies.push(discrepancy(
+                "total_unstaked_and_waiting == sum(sp.unstaked)",
+                self.total_unstaked_and_waiting,
+                sum_pools_unstaked,
+            ));
+        }
+
+        //---- sums, once all the items were walked
+        let complete = next_index >= total_items;
+        if complete {
+            if sum_available != self.total_available {
+                discrepancies.push(discrepancy(
+                    "total_available == sum(account.available)",
+                    self.total_available,
+                    sum_available,
+                ));
+            }
+            if sum_unstaked + sum_unstake_tickets != self.total_unstake_claims {
+                discrepancies.push(discrepancy(
+                    "total_unstake_claims == sum(account.unstaked) + sum(ticket.amount)",
+                    self.total_unstake_claims,
+                    sum_unstaked + sum_unstake_tickets,
+                ));
+            }
+            if sum_stake_shares != self.total_stake_shares {
+                discrepancies.push(discrepancy(
+                    "total_stake_shares == sum(account.stake_shares)",
+                    self.total_stake_shares,
+                    sum_stake_shares,
+                ));
+            }
+        }
+
+        return InvariantsCheckJSON {
+            next_index,
+            total_items,
+            complete,
+            sums: InvariantSumsJSON {
+                available: sum_available.into(),
+                unstaked: sum_unstaked.into(),
+                stake_shares: sum_stake_shares.into(),
+                unstake_tickets: sum_unstake_tickets.into(),
+            },
+            discrepancies,
+        };
+    }
+}
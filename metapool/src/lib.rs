@@ -12,7 +12,8 @@ const DEFAULT_WEB_APP_URL: &str = "https://metapool.app";
 const DEFAULT_AUDITOR_ACCOUNT_ID: &str = "auditors.near";
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::Base58PublicKey;
 use near_sdk::{env, ext_contract, log, near_bindgen, AccountId, PanicOnDefault, Promise};
 
@@ -46,6 +47,22 @@ pub use validator_loans::*;
 
 pub mod empty_nep_145;
 pub mod fungible_token_standard;
+use fungible_token_standard::ft_metadata_default;
+pub mod fungible_token_receiver;
+
+pub mod unstake_tickets;
+pub use unstake_tickets::*;
+
+pub mod quick_exit;
+
+pub mod invariants;
+
+pub mod price_history;
+pub use price_history::*;
+pub mod sp_performance;
+pub use sp_performance::*;
+pub mod auto_weights;
+pub use auto_weights::*;
 
 //mod migrations;
 
@@ -58,20 +75,59 @@ near_sdk::setup_alloc!();
 pub trait ExtMetaStakingPoolOwnerCallbacks {
     fn on_staking_pool_deposit(&mut self, amount: U128String) -> bool;
 
-    fn on_retrieve_from_staking_pool(&mut self, inx: u16) -> bool;
+    fn on_retrieve_from_staking_pool(
+        &mut self,
+        inx: u16,
+        amount: U128String,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> U128String;
+
+    fn on_retrieve_all_matured(
+        &mut self,
+        sp_inxs: Vec<u16>,
+        amounts: Vec<U128String>,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> Vec<RetrieveReportJSON>;
 
     fn on_staking_pool_stake_maybe_deposit(
         &mut self,
         sp_inx: usize,
         amount: u128,
         included_deposit: bool,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        keeper_id: Option<AccountId>,
     ) -> bool;
 
-    fn on_staking_pool_unstake(&mut self, sp_inx: usize, amount: U128String) -> bool;
+    fn on_staking_pool_unstake(
+        &mut self,
+        sp_inx: usize,
+        amount: U128String,
+        contract_locked: bool,
+        lock_since: BlockHeight,
+        kind: UnstakeKind,
+        keeper_id: Option<AccountId>,
+    ) -> bool;
 
     fn on_get_result_from_transfer_poll(&mut self, #[callback] poll_result: PollResult) -> bool;
 
-    fn on_get_sp_total_balance(&mut self, sp_inx: usize, #[callback] total_balance: U128String);
+    fn on_get_sp_total_balance(
+        &mut self,
+        sp_inx: usize,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+        #[callback] total_balance: U128String,
+    );
+
+    fn on_get_sp_total_balance_batch(
+        &mut self,
+        sp_inxs: Vec<u16>,
+        lock_since: BlockHeight,
+        keeper_id: AccountId,
+    ) -> u16;
 
     fn on_get_sp_unstaked_balance(
         &mut self,
@@ -79,7 +135,41 @@ pub trait ExtMetaStakingPoolOwnerCallbacks {
         #[callback] unstaked_balance: U128String,
     );
 
+    fn on_resync_pool(&mut self, sp_inx: usize, lock_since: BlockHeight);
+
+    fn on_get_sp_reward_fee_fraction(
+        &mut self,
+        sp_account_id: AccountId,
+        #[callback] reward_fee_fraction: RewardFeeFraction,
+    );
+
     fn after_minting_meta(self, account_id: AccountId, to_mint: U128String);
+
+    fn on_whitelist_is_whitelisted(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        #[callback] is_whitelisted: bool,
+    ) -> bool;
+
+    fn on_wnear_unwrapped_for_stake(&mut self, sender_id: AccountId, amount: U128String) -> U128String;
+
+    fn on_wnear_rewrapped_for_refund(&mut self, sender_id: AccountId, amount: U128String) -> U128String;
+
+    fn on_wnear_deposited_for_transfer(
+        &mut self,
+        beneficiary_id: AccountId,
+        amount: U128String,
+        wnear_contract_id: AccountId,
+    );
+
+    fn on_wnear_transfer_resolved(
+        &mut self,
+        beneficiary_id: AccountId,
+        amount: U128String,
+        wnear_contract_id: AccountId,
+    );
+
+    fn on_wnear_unwrapped_for_refund(&mut self, beneficiary_id: AccountId, amount: U128String);
 }
 
 #[ext_contract(meta_token_mint)]
@@ -120,6 +210,12 @@ pub struct MetaPool {
     /// Note: There's a extra functionality (quick-exit) that can speed-up unstaking claims if there's funds in this amount.
     pub reserve_for_unstake_claims: u128,
 
+    /// amount of the delayed-unstake claims (incl. tickets) by unlock epoch, only for future epochs
+    /// used to project which part of reserve_for_unstake_claims is not needed before the unstaking funds are back (quick-exit)
+    pub unstake_claims_by_unlock_epoch: Vec<(EpochHeight, u128)>,
+    /// quick-exit fee for each epoch remaining until the claim unlocks. Goes to the NSLP
+    pub quick_exit_fee_bp_per_epoch: u16,
+
     /// This value is equivalent to sum(accounts.available)
     /// This amount increments with user's deposits_into_available and decrements when users stake_from_available
     /// increments with unstake_to_available and decrements with withdraw_from_available
@@ -159,8 +255,8 @@ pub struct MetaPool {
     /// The total amount of tokens actually unstaked and in the waiting-delay (the tokens are in the staking pools)
     pub total_unstaked_and_waiting: u128,
 
-    /// sum(accounts.unstake). Every time a user delayed-unstakes, this amount is incremented
-    /// when the funds are withdrawn the amount is decremented.
+    /// sum(accounts.unstake) + sum(unstake_tickets.amount). Every time a user delayed-unstakes, this amount is incremented
+    /// when the funds are withdrawn (or a ticket redeemed) the amount is decremented.
     /// Control: total_unstaked_claims == reserve_for_unstaked_claims + total_unstaked_and_waiting
     pub total_unstake_claims: u128,
 
@@ -171,6 +267,17 @@ pub struct MetaPool {
     //user's accounts
     pub accounts: UnorderedMap<AccountId, Account>,
 
+    /// pending delayed-unstake claims by account. sum(claims.amount) == account.unstaked
+    /// (kept out of Account, so adding claims does not require an accounts migration)
+    pub unstake_claims: LookupMap<AccountId, Vec<UnstakeClaim>>,
+
+    /// delayed-unstake claims minted as NEP-171 tokens, by token_id
+    pub unstake_tickets: UnorderedMap<TokenId, UnstakeTicket>,
+    /// NEP-181 enumeration, token_ids by owner
+    pub unstake_tickets_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    /// last token_id minted
+    pub unstake_ticket_last_id: u64,
+
     //list of pools to diversify in
     pub staking_pools: Vec<StakingPoolInfo>,
 
@@ -209,6 +316,12 @@ pub struct MetaPool {
     pub treasury_account_id: AccountId,
     /// treasury cut on Liquid Unstake (25% from the fees by default)
     pub treasury_swap_cut_basis_points: u16,
+    /// part of the treasury cut on Liquid Unstake that goes to the insurance fund
+    pub insurance_swap_cut_basis_points: u16,
+    /// insurance fund fee on staking rewards (minted stNEAR, like the operator fee)
+    pub insurance_rewards_fee_basis_points: u16,
+    /// max NEAR covered by the insurance fund on each loss reported by a staking pool (0 = losses are fully socialized)
+    pub loss_coverage_cap: u128,
 
     // Configurable info for [NEP-129](https://github.com/nearprotocol/NEPs/pull/129)
     pub web_app_url: Option<String>,
@@ -217,6 +330,56 @@ pub struct MetaPool {
     /// Where's the NEP-141 $META token contract
     pub meta_token_account_id: AccountId,
 
+    /// stNEAR NEP-148 metadata, set by the owner
+    pub ft_metadata: LazyOption<FungibleTokenMetadata>,
+
+    /// add_staking_pool accepts only these factory suffixes (starting with ".") or exact accounts
+    pub staking_pool_allowed_accounts: Vec<String>,
+
+    /// staking-pools whitelist contract (lockup-whitelist.near). If set, add_staking_pool only adds whitelisted pools
+    pub whitelist_account_id: Option<AccountId>,
+
+    /// Where's the NEP-141 wNEAR contract (wrap.near). wNEAR received via ft_transfer_call can be staked
+    pub wnear_contract_id: Option<AccountId>,
+
+    /// stNEAR price snapshots, one per epoch, ring buffer of PRICE_HISTORY_MAX_LEN
+    pub price_history: Vector<PriceSnapshot>,
+    /// index of the latest snapshot in price_history
+    pub price_history_last_inx: u64,
+
+    /// rewards history & performance counters per staking pool account
+    pub sp_performance: LookupMap<AccountId, StakingPoolPerformance>,
+
+    /// automatic weights from performance: permissionless auto_rebalance_weights once per epoch
+    pub auto_weights_enabled: bool,
+    /// min number of pools with weight > 0 after an automatic rebalance
+    pub auto_weights_min_pools: u16,
+    pub auto_weights_last_epoch: EpochHeight,
+    /// (pool account_id, weight_basis_points) fixed manually, not changed by auto_rebalance_weights
+    pub sp_weight_overrides: Vec<(AccountId, u16)>,
+
+    /// max validator fee accepted. check_pool_fee sets weight 0 to pools with a higher fee
+    pub max_sp_fee_basis_points: u16,
+
+    /// rebalance_unstake budget: max basis points of total_actually_staked unstaked per epoch
+    pub rebalance_max_bp_per_epoch: u16,
+    pub rebalance_epoch: EpochHeight,
+    pub rebalance_unstaked_this_epoch: u128,
+
+    /// distribute_staking/unstaking: max pools to stake/unstake at once, locking only the pools. 1 = one pool per call, locking the contract
+    pub max_parallel_pools: u16,
+
+    /// block when contract_busy was set
+    pub contract_busy_since: BlockHeight,
+    /// contract & pool locks expire after this many blocks (a callback that never ran). see clear_stale_locks
+    pub busy_lock_expiry_blocks: u64,
+
+    /// paid from the extra balance to callers of the heartbeat fns that did some work. 0 = disabled
+    pub keeper_bounty: u128,
+    pub keeper_bounty_max_per_epoch: u128,
+    pub keeper_bounty_epoch: EpochHeight,
+    pub keeper_bounty_paid_this_epoch: u128,
+
     /// estimated & max meta rewards for each category
     pub est_meta_rewards_stakers: u128,
     pub est_meta_rewards_lu: u128, //liquid-unstakers
@@ -268,12 +431,17 @@ impl MetaPool {
             operator_rewards_fee_basis_points: DEFAULT_OPERATOR_REWARDS_FEE_BASIS_POINTS,
             operator_swap_cut_basis_points: DEFAULT_OPERATOR_SWAP_CUT_BASIS_POINTS,
             treasury_swap_cut_basis_points: DEFAULT_TREASURY_SWAP_CUT_BASIS_POINTS,
+            insurance_swap_cut_basis_points: 0,
+            insurance_rewards_fee_basis_points: 0,
+            loss_coverage_cap: 0,
             staking_paused: false,
             total_available: 0,
             total_for_staking: 0,
             total_actually_staked: 0,
             total_unstaked_and_waiting: 0,
             reserve_for_unstake_claims: 0,
+            unstake_claims_by_unlock_epoch: Vec::new(),
+            quick_exit_fee_bp_per_epoch: DEFAULT_QUICK_EXIT_FEE_BP_PER_EPOCH,
             total_unstake_claims: 0,
             epoch_stake_orders: 0,
             epoch_unstake_orders: 0,
@@ -282,6 +450,10 @@ impl MetaPool {
             total_stake_shares: 0,
             total_meta: 0,
             accounts: UnorderedMap::new(b"A".to_vec()),
+            unstake_claims: LookupMap::new(b"U".to_vec()),
+            unstake_tickets: UnorderedMap::new(b"T".to_vec()),
+            unstake_tickets_per_owner: LookupMap::new(b"W".to_vec()),
+            unstake_ticket_last_id: 0,
             loan_requests: LookupMap::new(b"L".to_vec()),
             nslp_liquidity_target: 10_000 * NEAR,
             nslp_max_discount_basis_points: 180, //1.8%
@@ -295,6 +467,28 @@ impl MetaPool {
             lp_provider_meta_mult_pct: 200, //20x
             staking_pools: Vec::new(),
             meta_token_account_id,
+            ft_metadata: LazyOption::new(b"M".to_vec(), Some(&ft_metadata_default())),
+            staking_pool_allowed_accounts: default_staking_pool_allowed_accounts(),
+            whitelist_account_id: None,
+            wnear_contract_id: None,
+            price_history: Vector::new(b"H".to_vec()),
+            price_history_last_inx: 0,
+            sp_performance: LookupMap::new(b"P".to_vec()),
+            auto_weights_enabled: false,
+            auto_weights_min_pools: DEFAULT_AUTO_WEIGHTS_MIN_POOLS,
+            auto_weights_last_epoch: 0,
+            sp_weight_overrides: Vec::new(),
+            max_sp_fee_basis_points: DEFAULT_MAX_SP_FEE_BASIS_POINTS,
+            rebalance_max_bp_per_epoch: DEFAULT_REBALANCE_MAX_BP_PER_EPOCH,
+            rebalance_epoch: 0,
+            rebalance_unstaked_this_epoch: 0,
+            max_parallel_pools: 1,
+            contract_busy_since: 0,
+            busy_lock_expiry_blocks: DEFAULT_BUSY_LOCK_EXPIRY_BLOCKS,
+            keeper_bounty: 0,
+            keeper_bounty_max_per_epoch: 0,
+            keeper_bounty_epoch: 0,
+            keeper_bounty_paid_this_epoch: 0,
             est_meta_rewards_stakers: 0,
             est_meta_rewards_lu: 0,
             est_meta_rewards_lp: 0,
@@ -346,10 +540,19 @@ impl MetaPool {
 
     /// user method - simplified flow
     /// completes delayed-unstake action by transferring from retrieved_from_the_pools to user's NEAR account
-    /// equivalent to core-contracts/staking-pool.withdraw_all
+    /// pays out every matured unstake claim, the claims still in the waiting period are kept
     pub fn withdraw_unstaked(&mut self) -> Promise {
-        let account = self.internal_get_account(&env::predecessor_account_id());
-        self.internal_withdraw_use_unstaked(account.unstaked)
+        let account_id = env::predecessor_account_id();
+        let account = self.internal_get_account(&account_id);
+        let claims = self.internal_get_unstake_claims(&account_id, &account);
+        let epoch = env::epoch_height();
+        let matured = matured_claims_amount(&claims, epoch);
+        assert!(
+            matured > 0 || account.unstaked == 0,
+            "The unstaked balance is not yet available due to unstaking delay. You need to wait at least {} epochs",
+            epochs_to_next_unlock(&claims, epoch)
+        );
+        self.internal_withdraw_use_unstaked(matured)
     }
 
     /// meta-pool extension: Withdraws from "available" balance
@@ -384,14 +587,90 @@ impl MetaPool {
         let account_id = env::predecessor_account_id();
         let account = self.internal_get_account(&account_id);
         let amount = self.amount_from_stake_shares(account.stake_shares);
-        self.internal_unstake(amount);
+        self.internal_unstake(amount, false);
     }
 
     /// Unstakes the given amount from the inner account of the predecessor.
     /// The inner account should have enough staked balance.
     /// The new total unstaked balance will be available for withdrawal in four epochs.
-    pub fn unstake(&mut self, amount: U128String) {
-        self.internal_unstake(amount.0);
+    /// meta-pool extension: if `mint_ticket` is true, the claim is minted as a transferable NEP-171 unstake ticket
+    /// instead of being added to the account's unstaked balance. The ticket is redeemed with `redeem_unstake_ticket`
+    /// minting a ticket requires attaching UNSTAKE_TICKET_STORAGE_DEPOSIT, returned on redeem
+    #[payable]
+    pub fn unstake(&mut self, amount: U128String, mint_ticket: Option<bool>) {
+        let as_ticket = mint_ticket.unwrap_or(false);
+        if !as_ticket {
+            assert_eq!(env::attached_deposit(), 0, "Attach a deposit only to mint a ticket");
+        }
+        self.internal_unstake(amount.0, as_ticket);
+    }
+
+    /// meta-pool extension: sells to the NSLP as much stNEAR as it can absorb with a fee <= max_fee_bp (liquid unstake)
+    /// and delayed-unstakes the rest
+    pub fn smart_unstake(&mut self, st_near_to_burn: U128String, max_fee_bp: u16) -> SmartUnstakeResult {
+        self.assert_not_busy();
+
+        let account_id = env::predecessor_account_id();
+        let acc = self.internal_get_account(&account_id);
+        let st_near_to_burn = if is_close(st_near_to_burn.0, acc.stake_shares) {
+            acc.stake_shares
+        } else {
+            st_near_to_burn.0
+        };
+        assert!(
+            acc.stake_shares >= st_near_to_burn,
+            "Not enough stNEAR. You own {}",
+            acc.stake_shares
+        );
+
+        //how much can the NSLP absorb under the fee cap
+        let nslp_account = self.internal_get_nslp_account();
+        let max_nears = self.internal_max_nears_for_fee(nslp_account.available, max_fee_bp);
+        let mut st_near_to_sell =
+            std::cmp::min(st_near_to_burn, self.stake_shares_from_amount(max_nears));
+        if st_near_to_sell > 0 {
+            //skip a liquid part so small the swap fee rounds to 0 stNEAR (internal_liquid_unstake requires a fee)
+            let nears_out = self.amount_from_stake_shares(st_near_to_sell);
+            let fee_bp = self.internal_get_discount_basis_points(nslp_account.available, nears_out);
+            if self.stake_shares_from_amount(apply_pct(fee_bp, nears_out)) == 0 {
+                st_near_to_sell = 0;
+            }
+        }
+
+        let mut liquid = LiquidUnstakeResult {
+            near: 0.into(),
+            fee: 0.into(),
+            meta: 0.into(),
+        };
+        if st_near_to_sell > 0 {
+            liquid = self.internal_liquid_unstake(&account_id, st_near_to_sell, 0, true);
+            self.native_transfer_to_predecessor(liquid.near.0);
+        }
+
+        //the rest goes to a delayed-unstake claim
+        //computed from the shares actually burned (internal_liquid_unstake can round up to the whole balance)
+        let shares_after_liquid = self.internal_get_account(&account_id).stake_shares;
+        let st_near_sold = acc.stake_shares - shares_after_liquid;
+        let st_near_to_delay =
+            std::cmp::min(st_near_to_burn.saturating_sub(st_near_sold), shares_after_liquid);
+        let amount_to_delay = self.amount_from_stake_shares(st_near_to_delay);
+        let (delayed_amount, unlock_epoch) =
+            if amount_to_delay > 0 && self.stake_shares_from_amount(amount_to_delay) > 0 {
+                self.internal_unstake(amount_to_delay, false)
+            } else {
+                (0, 0)
+            };
+        let st_near_delayed = shares_after_liquid - self.internal_get_account(&account_id).stake_shares;
+
+        return SmartUnstakeResult {
+            liquid_unstaked_stnear: st_near_sold.into(),
+            near: liquid.near,
+            fee: liquid.fee,
+            meta: liquid.meta,
+            delayed_unstaked_stnear: st_near_delayed.into(),
+            delayed_unstake_amount: delayed_amount.into(),
+            unlock_epoch: unlock_epoch.into(),
+        };
     }
 
     /*****************************/
@@ -566,188 +845,40 @@ impl MetaPool {
         // so let's remove the one_yocto_requirement, waiting for a better solution for the function-call keys NEP-141 problem
         //assert_one_yocto();
 
-        let account_id = env::predecessor_account_id();
-        let mut user_account = self.internal_get_account(&account_id);
-
-        let stnear_owned = user_account.stake_shares;
-
-        let st_near_to_sell:u128 =
-        // if the amount is close to user's total, remove user's total
-        // to: a) do not leave less than ONE_MILLI_NEAR in the account, b) Allow 10 yoctos of rounding, e.g. remove(100) removes 99.999993 without panicking
-        if is_close(st_near_to_burn.0, stnear_owned) { // allow for rounding simplification
-            stnear_owned
-        }
-        else  {
-            st_near_to_burn.0
-        };
-
-        debug!(
-            "st_near owned:{}, to_sell:{}",
-            user_account.stake_shares, st_near_to_sell
-        );
-
-        assert!(
-            stnear_owned >= st_near_to_sell,
-            "Not enough stNEAR. You own {}",
-            stnear_owned
-        );
-
-        let mut nslp_account = self.internal_get_nslp_account();
-
-        //compute how many nears are the st_near valued at
-        let nears_out = self.amount_from_stake_shares(st_near_to_sell);
-        let swap_fee_basis_points =
-            self.internal_get_discount_basis_points(nslp_account.available, nears_out);
-        assert!(swap_fee_basis_points < 10000, "inconsistency d>1");
-        let fee = apply_pct(swap_fee_basis_points, nears_out);
-
-        let near_to_receive = nears_out - fee;
-        assert!(
-            near_to_receive >= min_expected_near.0,
-            "Price changed, your min amount {} is not satisfied {}. Try again",
+        let result = self.internal_liquid_unstake(
+            &env::predecessor_account_id(),
+            st_near_to_burn.0,
             min_expected_near.0,
-            near_to_receive
+            true,
         );
-        assert!(
-            nslp_account.available >= near_to_receive,
-            "Not enough liquidity in the liquidity pool"
-        );
-
-        //the NEAR for the user comes from the LP
-        nslp_account.available -= near_to_receive;
-        user_account.available += near_to_receive;
-
-        // keep track of meta rewards for LPs
-        self.est_meta_rewards_lp += damp_multiplier(
-            fee,
-            self.lp_provider_meta_mult_pct,
-            self.est_meta_rewards_lp,
-            self.max_meta_rewards_lp,
-        );
-
-        // compute how many shares the swap fee represent
-        let fee_in_st_near = self.stake_shares_from_amount(fee);
-
-        // involved accounts
-        assert!(
-            &account_id != &self.treasury_account_id,
-            "can't use treasury account"
-        );
-        let mut treasury_account = self.internal_get_account(&self.treasury_account_id);
-        assert!(
-            &account_id != &self.operator_account_id,
-            "can't use operator account"
-        );
-        let mut operator_account = self.internal_get_account(&self.operator_account_id);
-        assert!(
-            &account_id != &DEVELOPERS_ACCOUNT_ID,
-            "can't use developers account"
-        );
-        let mut developers_account = self.internal_get_account(&DEVELOPERS_ACCOUNT_ID.into());
-
-        // The treasury cut in stnear-shares (25% by default)
-        let treasury_st_near_cut = apply_pct(self.treasury_swap_cut_basis_points, fee_in_st_near);
-        treasury_account.add_st_near(treasury_st_near_cut, &self);
-
-        // The cut that the contract owner (operator) takes. (3% of 1% normally)
-        let operator_st_near_cut = apply_pct(self.operator_swap_cut_basis_points, fee_in_st_near);
-        operator_account.add_st_near(operator_st_near_cut, &self);
-
-        // The cut that the developers take. (2% of 1% normally)
-        let developers_st_near_cut = apply_pct(DEVELOPERS_SWAP_CUT_BASIS_POINTS, fee_in_st_near);
-        developers_account.add_st_near(developers_st_near_cut, &self);
-
-        // all the realized meta from non-liq.provider cuts (30%), send to operator & developers
-        let st_near_non_lp_cut =
-            treasury_st_near_cut + operator_st_near_cut + developers_st_near_cut;
-        let meta_from_operation = damp_multiplier(
-            st_near_non_lp_cut,
-            self.lp_provider_meta_mult_pct,
-            self.est_meta_rewards_lp,
-            self.max_meta_rewards_lp,
-        );
-        self.total_meta += meta_from_operation;
-        operator_account.realized_meta += meta_from_operation / 2;
-        developers_account.realized_meta += meta_from_operation / 2;
-
-        debug!("treasury_st_near_cut:{} operator_st_near_cut:{} developers_st_near_cut:{} fee_in_st_near:{}",
-            treasury_st_near_cut,operator_st_near_cut,developers_st_near_cut,fee_in_st_near);
-
-        assert!(
-            fee_in_st_near > treasury_st_near_cut + developers_st_near_cut + operator_st_near_cut
-        );
-
-        // The rest of the st_near sold goes into the liq-pool. Because it is a larger amount than NEARs removed, it will increase share value for all LP providers.
-        // Adding value to the pool via adding more stNEAR value than the NEAR removed, will be counted as rewards for the nslp_meter,
-        // so $META for LP providers will be created. $METAs for LP providers are realized during add_liquidity(), remove_liquidity()
-        let st_near_to_liq_pool = st_near_to_sell
-            - (treasury_st_near_cut + operator_st_near_cut + developers_st_near_cut);
-        debug!("nslp_account.add_st_near {}", st_near_to_liq_pool);
-        // major part of stNEAR sold goes to the NSLP
-        nslp_account.add_st_near(st_near_to_liq_pool, &self);
-
-        //complete the transfer, remove stnear from the user (stnear was transferred to the LP & others)
-        user_account.sub_st_near(st_near_to_sell, &self);
-        //mint $META for the selling user
-        let meta_to_seller = damp_multiplier(
-            fee_in_st_near,
-            self.stnear_sell_meta_mult_pct,
-            self.est_meta_rewards_lu,
-            self.max_meta_rewards_lu,
-        );
-        self.total_meta += meta_to_seller;
-        // keep track of meta rewards for lu's
-        self.est_meta_rewards_lu += meta_to_seller;
-        user_account.realized_meta += meta_to_seller;
-
-        //Save involved accounts
-        self.internal_update_account(&self.treasury_account_id.clone(), &treasury_account);
-        self.internal_update_account(&self.operator_account_id.clone(), &operator_account);
-        self.internal_update_account(&DEVELOPERS_ACCOUNT_ID.into(), &developers_account);
-        //Save nslp accounts
-        self.internal_save_nslp_account(&nslp_account);
 
         //simplified user-flow
         //direct transfer to user (instead of leaving it in-contract as "available")
-        let transfer_amount = user_account.take_from_available(near_to_receive, self);
-        self.native_transfer_to_predecessor(transfer_amount);
-
-        //Save user account
-        self.internal_update_account(&account_id, &user_account);
-
-        log!(
-            "@{} liquid-unstaked {} stNEAR, got {} NEAR and {} $META",
-            &account_id,
-            st_near_to_sell,
-            transfer_amount,
-            meta_to_seller
-        );
-        event!(
-            r#"{{"event":"LIQ.U","account_id":"{}","stnear":"{}","near":"{}"}}"#,
-            &account_id,
-            st_near_to_sell,
-            transfer_amount
-        );
+        self.native_transfer_to_predecessor(result.near.0);
 
-        return LiquidUnstakeResult {
-            near: transfer_amount.into(),
-            fee: fee_in_st_near.into(),
-            meta: meta_to_seller.into(),
-        };
+        return result;
     }
 
     /// add liquidity - payable
+    /// min_shares: slippage guard (front-run/end-run sandwiches), panics (and refunds) if the liquidity-pool shares received are less
     #[payable]
-    pub fn nslp_add_liquidity(&mut self) -> u16 {
-        // TODO: Since this method doesn't guard the resulting liquidity, is it possible to put it
-        //    into a front-run/end-run sandwich to capitalize on the transaction?
+    pub fn nslp_add_liquidity(&mut self, min_shares: Option<U128String>) -> u16 {
         self.internal_deposit();
-        return self.internal_nslp_add_liquidity(env::attached_deposit());
+        return self.internal_nslp_add_liquidity(
+            env::attached_deposit(),
+            min_shares.map(|x| x.0).unwrap_or(0),
+        );
     }
 
     /// remove liquidity from liquidity pool
+    /// min_expected_near & min_expected_stnear: slippage guard, panics if the NEAR or stNEAR received are less
     //#[payable]
-    pub fn nslp_remove_liquidity(&mut self, amount: U128String) -> RemoveLiquidityResult {
+    pub fn nslp_remove_liquidity(
+        &mut self,
+        amount: U128String,
+        min_expected_near: Option<U128String>,
+        min_expected_stnear: Option<U128String>,
+    ) -> RemoveLiquidityResult {
         self.assert_not_busy();
         //assert_one_yocto();
 
@@ -799,6 +930,21 @@ impl MetaPool {
         );
         let near_to_remove = to_remove - near_value_of_st_near;
 
+        let min_expected_near = min_expected_near.map(|x| x.0).unwrap_or(0);
+        assert!(
+            near_to_remove >= min_expected_near,
+            "Price changed, your min NEAR amount {} is not satisfied {}. Try again",
+            min_expected_near,
+            near_to_remove
+        );
+        let min_expected_stnear = min_expected_stnear.map(|x| x.0).unwrap_or(0);
+        assert!(
+            st_near_to_remove_from_pool >= min_expected_stnear,
+            "Price changed, your min stNEAR amount {} is not satisfied {}. Try again",
+            min_expected_stnear,
+            st_near_to_remove_from_pool
+        );
+
         //update user account
         //remove first from stNEAR in the pool, proportional to shares being burned
         //NOTE: To simplify user-operations, the LIQ.POOL DO NOT carry "unstaked". The NSLP self-balances only by internal-clearing on `deposit_and_stake`
@@ -819,12 +965,13 @@ impl MetaPool {
         self.internal_update_account(&account_id, &acc);
         self.internal_save_nslp_account(&nslp_account);
 
-        event!(
-            r#"{{"event":"REM.L","account_id":"{}","near":"{}","stnear":"{}"}}"#,
-            account_id,
-            transfer_amount,
-            st_near_to_remove_from_pool
-        );
+        event!("remove_liquidity", {
+            "account_id": account_id,
+            "near": transfer_amount.to_string(),
+            "stnear": st_near_to_remove_from_pool.to_string(),
+            "shares": nslp_shares_to_burn.to_string(),
+        });
+        log_ft_transfer(NSLP_INTERNAL_ACCOUNT, &account_id, st_near_to_remove_from_pool);
 
         return RemoveLiquidityResult {
             near: transfer_amount.into(),
@@ -842,7 +989,7 @@ impl MetaPool {
             from_index..std::cmp::min(from_index + limit, self.accounts.keys_as_vector().len())
         {
             let account_id = &self.accounts.keys_as_vector().get(inx).unwrap();
-            if account_id == NSLP_INTERNAL_ACCOUNT {
+            if account_id == NSLP_INTERNAL_ACCOUNT || account_id == INSURANCE_INTERNAL_ACCOUNT {
                 continue;
             }
             let mut acc = self.internal_get_account(&account_id);
@@ -862,6 +1009,7 @@ impl MetaPool {
     pub fn realize_meta(&mut self, account_id: String) {
         // this fn shoudl not be called for the NSLP_INTERNAL_ACCOUNT
         assert!(account_id!=NSLP_INTERNAL_ACCOUNT);
+        assert!(account_id!=INSURANCE_INTERNAL_ACCOUNT);
 
         let mut acc = self.internal_get_account(&account_id);
 
@@ -1003,7 +1151,7 @@ impl MetaPool {
 mod tests {
     //use std::convert::TryInto;
 
-    use near_sdk::{testing_env, MockedBlockchain, VMContext}; //PromiseResult,
+    use near_sdk::{testing_env, MockedBlockchain, PromiseResult, VMContext};
 
     mod unit_test_utils;
     use unit_test_utils::*;
@@ -1115,4 +1263,470 @@ mod tests {
         rm.stake(10);
         assert_eq!(rm.compute_rewards(11, 500, 1000), 60);
     }
+
+    #[test]
+    fn test_unstake_claims() {
+        let mut claims: Vec<UnstakeClaim> = Vec::new();
+        add_unstake_claim(&mut claims, 100, 14);
+        add_unstake_claim(&mut claims, 50, 18);
+        add_unstake_claim(&mut claims, 10, 14); // same epoch, merged
+        assert_eq!(claims.len(), 2);
+
+        assert_eq!(matured_claims_amount(&claims, 13), 0);
+        assert_eq!(epochs_to_next_unlock(&claims, 13), 1);
+        assert_eq!(matured_claims_amount(&claims, 14), 110);
+        assert_eq!(epochs_to_next_unlock(&claims, 14), 4);
+        assert_eq!(matured_claims_amount(&claims, 20), 160);
+    }
+
+    #[test]
+    fn test_take_immature_claims() {
+        let mut claims: Vec<UnstakeClaim> = Vec::new();
+        add_unstake_claim(&mut claims, 50, 18);
+        add_unstake_claim(&mut claims, 100, 10);
+        add_unstake_claim(&mut claims, 30, 14);
+
+        // at epoch 12 the claim for epoch 10 is matured, quick-exit takes from epoch 14 first
+        let parts = take_immature_claims(&mut claims, 40, 12);
+        assert_eq!(
+            parts,
+            vec![
+                UnstakeClaim { amount: 30, unlock_epoch: 14 },
+                UnstakeClaim { amount: 10, unlock_epoch: 18 }
+            ]
+        );
+        assert_eq!(claims.len(), 2);
+        assert_eq!(matured_claims_amount(&claims, 12), 100);
+        assert_eq!(matured_claims_amount(&claims, 18), 140);
+    }
+
+    #[test]
+    fn test_compute_auto_weights() {
+        assert_eq!(
+            compute_auto_weights(&vec![100, 100, 100, 0], 10000),
+            Some(vec![3334, 3333, 3333, 0])
+        );
+        // the best pool is capped, the rest is split between the others
+        assert_eq!(
+            compute_auto_weights(&vec![1000, 1, 1], 10000),
+            Some(vec![4999, 2501, 2500])
+        );
+        // 2 pools can't take 100%
+        assert_eq!(compute_auto_weights(&vec![10, 10], 10000), None);
+        assert_eq!(compute_auto_weights(&vec![10, 10], 6000), Some(vec![3000, 3000]));
+    }
+
+    #[test]
+    fn test_set_sp_weight_keeps_busy_pools() {
+        let (_context, mut contract) = contract_only_setup();
+        for inx in 0..4 {
+            contract
+                .staking_pools
+                .push(StakingPoolInfo::new(format!("sp{}.pool.near", inx), 2500));
+        }
+        contract.staking_pools[1].lock();
+        // pool 0 to 0: its weight goes to the pools not busy
+        assert!(contract.internal_set_sp_weight(0, 0));
+        let weights: Vec<u16> = contract
+            .staking_pools
+            .iter()
+            .map(|sp| sp.weight_basis_points)
+            .collect();
+        assert_eq!(weights, vec![0, 2500, 3750, 3750]);
+    }
+
+    //-- pools released with an op in flight (clear_stale_locks) & resync_pool
+
+    /// a pool with 1000 NEAR staked, rewards distributed in this epoch
+    fn contract_with_pool() -> (VMContext, MetaPool) {
+        let (context, mut contract) = contract_only_setup();
+        let mut sp = StakingPoolInfo::new("sp0.pool.near".into(), 10000);
+        sp.staked = ntoy(1000);
+        sp.last_asked_rewards_epoch_height = context.epoch_height;
+        contract.staking_pools.push(sp);
+        contract.total_for_staking = ntoy(1000);
+        contract.total_actually_staked = ntoy(1000);
+        contract.contract_account_balance = ntoy(500);
+        return (context, contract);
+    }
+
+    /// the lock expired with `op` in flight (its preventive accounting is already applied)
+    fn set_op_in_flight(contract: &mut MetaPool, op: PendingOp) {
+        let sp = &mut contract.staking_pools[0];
+        sp.pending_op = op;
+        sp.needs_resync = true;
+    }
+
+    /// on_resync_pool with the balances informed by the pool
+    fn resync_with(context: &VMContext, contract: &mut MetaPool, staked: u128, unstaked: u128) {
+        let mut context = context.clone();
+        context.predecessor_account_id = contract_account();
+        let account = HumanReadableAccount {
+            account_id: contract_account(),
+            unstaked_balance: unstaked.into(),
+            staked_balance: staked.into(),
+            can_withdraw: true,
+        };
+        testing_env_with_promise_results(
+            context,
+            PromiseResult::Successful(near_sdk::serde_json::to_vec(&account).unwrap()),
+        );
+        // resync_pool locked the pool
+        contract.staking_pools[0].lock();
+        let lock_since = contract.staking_pools[0].busy_lock_since;
+        contract.on_resync_pool(0, lock_since);
+    }
+
+    fn assert_resynced(contract: &MetaPool) {
+        let sp = &contract.staking_pools[0];
+        assert!(!sp.needs_resync);
+        assert!(sp.pending_op.is_none());
+        assert!(!sp.is_busy());
+    }
+
+    #[test]
+    fn test_resync_stake_deposit() {
+        let (context, mut contract) = contract_with_pool();
+        contract.total_actually_staked = ntoy(1100);
+        set_op_in_flight(
+            &mut contract,
+            PendingOp::Stake {
+                amount: ntoy(100),
+                included_deposit: true,
+            },
+        );
+        resync_with(&context, &mut contract, ntoy(1100), 0);
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(1100));
+        assert_eq!(contract.total_actually_staked, ntoy(1100));
+        assert_eq!(contract.contract_account_balance, ntoy(400));
+
+        // failed: the deposit was refunded
+        let (context, mut contract) = contract_with_pool();
+        contract.total_actually_staked = ntoy(1100);
+        set_op_in_flight(
+            &mut contract,
+            PendingOp::Stake {
+                amount: ntoy(100),
+                included_deposit: true,
+            },
+        );
+        resync_with(&context, &mut contract, ntoy(1000), 0);
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(1000));
+        assert_eq!(contract.total_actually_staked, ntoy(1000));
+        assert_eq!(contract.epoch_stake_orders, ntoy(100));
+        assert_eq!(contract.contract_account_balance, ntoy(500));
+    }
+
+    #[test]
+    fn test_resync_stake_deposit_undecided() {
+        let stake = PendingOp::Stake {
+            amount: ntoy(100),
+            included_deposit: true,
+        };
+        // the pool got rewards: neither staked nor staked+amount, the operator decides
+        let (context, mut contract) = contract_with_pool();
+        contract.total_actually_staked = ntoy(1100);
+        set_op_in_flight(&mut contract, stake.clone());
+        resync_with(&context, &mut contract, ntoy(1003), 0);
+        assert!(contract.staking_pools[0].needs_resync);
+        assert!(contract.staking_pools[0].pending_op == stake);
+        assert!(!contract.staking_pools[0].busy_lock);
+
+        let mut operator_context = context.clone();
+        operator_context.predecessor_account_id = operator_account();
+        operator_context.attached_deposit = 1;
+        testing_env!(operator_context);
+        contract.resolve_pending_op(0, false);
+        assert_resynced(&contract);
+        assert_eq!(contract.total_actually_staked, ntoy(1000));
+        assert_eq!(contract.epoch_stake_orders, ntoy(100));
+
+        // rewards not distributed in this epoch: sp.staked can't be compared
+        let (context, mut contract) = contract_with_pool();
+        contract.staking_pools[0].last_asked_rewards_epoch_height = 0;
+        contract.total_actually_staked = ntoy(1100);
+        set_op_in_flight(&mut contract, stake.clone());
+        resync_with(&context, &mut contract, ntoy(1100), 0);
+        assert!(contract.staking_pools[0].needs_resync);
+        assert_eq!(contract.contract_account_balance, ntoy(500));
+    }
+
+    #[test]
+    fn test_resync_restake() {
+        let (context, mut contract) = contract_with_pool();
+        contract.staking_pools[0].unstaked = ntoy(200);
+        contract.total_unstaked_and_waiting = ntoy(200);
+        contract.total_actually_staked = ntoy(1100);
+        set_op_in_flight(
+            &mut contract,
+            PendingOp::Stake {
+                amount: ntoy(100),
+                included_deposit: false,
+            },
+        );
+        // rewards don't matter, the unstaked balance decides
+        resync_with(&context, &mut contract, ntoy(1105), ntoy(100));
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(1100));
+        assert_eq!(contract.staking_pools[0].unstaked, ntoy(100));
+        assert_eq!(contract.total_unstaked_and_waiting, ntoy(100));
+        assert_eq!(contract.reserve_for_unstake_claims, ntoy(100));
+    }
+
+    #[test]
+    fn test_resync_unstake() {
+        let unstake = PendingOp::Unstake {
+            amount: ntoy(100),
+            kind: UnstakeKind::Orders,
+        };
+        let (context, mut contract) = contract_with_pool();
+        contract.total_actually_staked = ntoy(900);
+        set_op_in_flight(&mut contract, unstake.clone());
+        resync_with(&context, &mut contract, ntoy(905), ntoy(100));
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(900));
+        assert_eq!(contract.staking_pools[0].unstaked, ntoy(100));
+        assert_eq!(contract.total_unstaked_and_waiting, ntoy(100));
+        assert_eq!(contract.total_actually_staked, ntoy(900));
+
+        // failed: the orders are back
+        let (context, mut contract) = contract_with_pool();
+        contract.total_actually_staked = ntoy(900);
+        set_op_in_flight(&mut contract, unstake);
+        resync_with(&context, &mut contract, ntoy(1005), 0);
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(1000));
+        assert_eq!(contract.total_actually_staked, ntoy(1000));
+        assert_eq!(contract.epoch_unstake_orders, ntoy(100));
+    }
+
+    #[test]
+    fn test_resync_withdraw() {
+        let (context, mut contract) = contract_with_pool();
+        contract.staking_pools[0].unstaked = ntoy(100);
+        contract.total_unstaked_and_waiting = ntoy(100);
+        set_op_in_flight(&mut contract, PendingOp::Withdraw { amount: ntoy(100) });
+        resync_with(&context, &mut contract, ntoy(1005), 0);
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].unstaked, 0);
+        assert_eq!(contract.total_unstaked_and_waiting, 0);
+        assert_eq!(contract.contract_account_balance, ntoy(600));
+        assert_eq!(contract.reserve_for_unstake_claims, ntoy(100));
+
+        // failed: still in the pool
+        let (context, mut contract) = contract_with_pool();
+        contract.staking_pools[0].unstaked = ntoy(100);
+        contract.total_unstaked_and_waiting = ntoy(100);
+        set_op_in_flight(&mut contract, PendingOp::Withdraw { amount: ntoy(100) });
+        resync_with(&context, &mut contract, ntoy(1005), ntoy(100));
+        assert_resynced(&contract);
+        assert_eq!(contract.staking_pools[0].unstaked, ntoy(100));
+        assert_eq!(contract.contract_account_balance, ntoy(500));
+        assert_eq!(contract.reserve_for_unstake_claims, 0);
+    }
+
+    fn at_block(context: &VMContext, block_index: u64) -> VMContext {
+        let mut context = context.clone();
+        context.block_index = block_index;
+        testing_env!(context.clone());
+        return context;
+    }
+
+    #[test]
+    fn test_assert_not_busy_releases_expired_lock() {
+        let (context, mut contract) = contract_with_pool();
+        contract.contract_busy = true;
+        contract.contract_busy_since = context.block_index;
+        contract.staking_pools[0].lock();
+        contract.staking_pools[0].pending_op = PendingOp::Withdraw { amount: ntoy(100) };
+        contract.staking_pools[0].pending_op_since = context.block_index;
+
+        at_block(&context, context.block_index + contract.busy_lock_expiry_blocks);
+        contract.assert_not_busy();
+        assert!(!contract.contract_busy);
+        // the pool lock expired too, with an op in flight
+        assert!(!contract.staking_pools[0].busy_lock);
+        assert!(contract.staking_pools[0].needs_resync);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is busy")]
+    fn test_assert_not_busy_lock_not_expired() {
+        let (context, mut contract) = contract_with_pool();
+        contract.contract_busy = true;
+        contract.contract_busy_since = context.block_index;
+        at_block(&context, context.block_index + contract.busy_lock_expiry_blocks - 1);
+        contract.assert_not_busy();
+    }
+
+    #[test]
+    fn test_late_callback_after_lock_expired() {
+        let (context, mut contract) = contract_with_pool();
+        let lock_since = context.block_index;
+        // direct_unstake of 100 at lock_since, contract locked
+        contract.contract_busy = true;
+        contract.contract_busy_since = lock_since;
+        contract.staking_pools[0].lock();
+        contract.staking_pools[0].pending_op = PendingOp::Unstake {
+            amount: ntoy(100),
+            kind: UnstakeKind::Orders,
+        };
+        contract.staking_pools[0].pending_op_since = lock_since;
+        contract.total_actually_staked = ntoy(900);
+        contract.staking_pools[0].last_asked_rewards_epoch_height = 0;
+
+        // expired & released, then the contract is locked again & the pool resync is in flight
+        let context = at_block(&context, lock_since + contract.busy_lock_expiry_blocks);
+        assert_eq!(contract.clear_stale_locks(), 2);
+        contract.contract_busy = true;
+        contract.contract_busy_since = context.block_index;
+        contract.staking_pools[0].lock();
+
+        // the callback of the unstake arrives late: settles the op, leaves the new locks alone
+        let mut callback_context = context.clone();
+        callback_context.predecessor_account_id = contract_account();
+        testing_env_with_promise_results(callback_context.clone(), PromiseResult::Successful(vec![]));
+        contract.on_staking_pool_unstake(
+            0,
+            ntoy(100).into(),
+            true,
+            lock_since,
+            UnstakeKind::Orders,
+            None,
+        );
+        assert!(contract.contract_busy);
+        assert!(contract.staking_pools[0].busy_lock);
+        assert!(!contract.staking_pools[0].needs_resync);
+        assert!(contract.staking_pools[0].pending_op.is_none());
+        assert_eq!(contract.staking_pools[0].staked, ntoy(900));
+        assert_eq!(contract.staking_pools[0].unstaked, ntoy(100));
+
+        // a total balance from a lock that expired is ignored
+        testing_env!(callback_context.clone());
+        contract.on_get_sp_total_balance(0, lock_since, operator_account(), ntoy(1010).into());
+        assert!(contract.contract_busy);
+        assert!(contract.staking_pools[0].busy_lock);
+        assert_eq!(contract.staking_pools[0].staked, ntoy(900));
+        assert_eq!(contract.staking_pools[0].last_asked_rewards_epoch_height, 0);
+
+        // the current locks are released by their own callback
+        contract.on_get_sp_total_balance(0, context.block_index, operator_account(), ntoy(1000).into());
+        assert!(!contract.contract_busy);
+        assert!(!contract.staking_pools[0].busy_lock);
+    }
+
+    //-- keeper bounty
+
+    #[test]
+    fn test_keeper_bounty_max_per_epoch() {
+        let (context, mut contract) = contract_only_setup();
+        // 10 NEAR of extra balance
+        contract.contract_account_balance = ntoy(TEST_INITIAL_BALANCE - 10);
+        contract.keeper_bounty = ntoy(1);
+        contract.keeper_bounty_max_per_epoch = ntoy(3);
+        let keeper: AccountId = "keeper".into();
+
+        contract.internal_pay_keeper_bounty(&keeper, "distribute_rewards", 2);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, ntoy(2));
+        // capped at max_per_epoch
+        contract.internal_pay_keeper_bounty(&keeper, "distribute_rewards", 2);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, ntoy(3));
+        contract.internal_pay_keeper_bounty(&keeper, "distribute_rewards", 1);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, ntoy(3));
+
+        // the cap resets on the next epoch
+        let mut context = context.clone();
+        context.epoch_height += 1;
+        testing_env!(context.clone());
+        contract.internal_pay_keeper_bounty(&keeper, "distribute_rewards", 1);
+        assert_eq!(contract.keeper_bounty_epoch, context.epoch_height);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, ntoy(1));
+    }
+
+    #[test]
+    fn test_keeper_bounty_limited_by_extra_balance() {
+        let (_context, mut contract) = contract_only_setup();
+        contract.keeper_bounty = ntoy(1);
+        contract.keeper_bounty_max_per_epoch = ntoy(10);
+        let keeper: AccountId = "keeper".into();
+
+        // no extra balance: nothing paid
+        contract.contract_account_balance = ntoy(TEST_INITIAL_BALANCE);
+        contract.internal_pay_keeper_bounty(&keeper, "retrieve_all_matured", 1);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, 0);
+
+        // half a NEAR of extra balance, 10 cents are kept for transfer fees
+        contract.contract_account_balance = ntoy(TEST_INITIAL_BALANCE) - ntoy(1) / 2;
+        contract.internal_pay_keeper_bounty(&keeper, "retrieve_all_matured", 1);
+        assert_eq!(contract.keeper_bounty_paid_this_epoch, ntoy(1) / 2 - 10 * NEAR_CENT);
+    }
+
+    //-- staking loss & insurance fund
+
+    /// 1000 NEAR staked at price 1: 50 stNEAR in the insurance fund, 950 stNEAR of the users
+    fn contract_with_insurance(insurance_shares: u128) -> (VMContext, MetaPool) {
+        let (context, mut contract) = contract_with_pool();
+        contract.total_stake_shares = ntoy(1000);
+        let insurance_account_id: AccountId = INSURANCE_INTERNAL_ACCOUNT.into();
+        let mut insurance_account = contract.internal_get_account(&insurance_account_id);
+        insurance_account.add_stake_shares(insurance_shares, insurance_shares);
+        contract.internal_update_account(&insurance_account_id, &insurance_account);
+        return (context, contract);
+    }
+
+    fn insurance_shares(contract: &MetaPool) -> u128 {
+        return contract
+            .internal_get_account(&INSURANCE_INTERNAL_ACCOUNT.into())
+            .stake_shares;
+    }
+
+    #[test]
+    fn test_staking_loss_covered_by_insurance() {
+        let (_context, mut contract) = contract_with_insurance(ntoy(50));
+        contract.loss_coverage_cap = ntoy(30);
+
+        // 40 lost: 30 covered (cap), 10 socialized
+        contract.internal_book_staking_loss(&"sp0.pool.near".into(), ntoy(40));
+        assert_eq!(insurance_shares(&contract), ntoy(20));
+        assert_eq!(contract.total_stake_shares, ntoy(970));
+        assert_eq!(contract.total_for_staking, ntoy(960));
+        assert_eq!(contract.total_actually_staked, ntoy(960));
+        // the users absorb only the socialized part, the price is 960/970
+        assert_eq!(
+            contract.amount_from_stake_shares(ntoy(950)),
+            proportional(ntoy(950), ntoy(960), ntoy(970))
+        );
+        assert_almost_eq_with_max_delta(
+            contract.amount_from_stake_shares(ntoy(950)),
+            ntoy(950) - proportional(ntoy(10), ntoy(950), ntoy(970)),
+            1000,
+        );
+
+        // a loss under the cap is fully covered, the price does not change
+        let price_before = contract.amount_from_stake_shares(ntoy(1));
+        contract.internal_book_staking_loss(&"sp0.pool.near".into(), ntoy(10) * 960 / 970);
+        assert_almost_eq_with_max_delta(contract.amount_from_stake_shares(ntoy(1)), price_before, 10);
+    }
+
+    #[test]
+    fn test_staking_loss_insurance_exhausted() {
+        let (_context, mut contract) = contract_with_insurance(ntoy(5));
+        contract.loss_coverage_cap = ntoy(30);
+
+        // 40 lost: only 5 covered (all the insurance fund), 35 socialized
+        contract.internal_book_staking_loss(&"sp0.pool.near".into(), ntoy(40));
+        assert_eq!(insurance_shares(&contract), 0);
+        assert_eq!(contract.total_stake_shares, ntoy(995));
+        assert_eq!(contract.total_for_staking, ntoy(960));
+        assert_eq!(contract.amount_from_stake_shares(ntoy(995)), ntoy(960));
+
+        // no insurance cap: all socialized
+        let (_context, mut contract) = contract_with_insurance(ntoy(50));
+        contract.internal_book_staking_loss(&"sp0.pool.near".into(), ntoy(40));
+        assert_eq!(insurance_shares(&contract), ntoy(50));
+        assert_eq!(contract.total_stake_shares, ntoy(1000));
+        assert_eq!(contract.total_for_staking, ntoy(960));
+    }
 }
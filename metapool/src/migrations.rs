@@ -4,11 +4,23 @@
 //-----------------------------
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, Vector};
 use near_sdk::{env, near_bindgen, AccountId, EpochHeight};
 
 use crate::*;
 
+/// PREVIOUS items in the Vec of staking pools (before evacuation_amount)
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldStakingPoolInfo {
+    pub account_id: AccountId,
+    pub weight_basis_points: u16,
+    pub busy_lock: bool,
+    pub staked: u128,
+    pub unstaked: u128,
+    pub unstk_req_epoch_height: EpochHeight,
+    pub last_asked_rewards_epoch_height: EpochHeight,
+}
+
 //---------------------------------------------------
 //  PREVIOUS Main Contract State for state migrations
 //---------------------------------------------------
@@ -92,7 +104,7 @@ pub struct OldMetaPool {
     pub accounts: UnorderedMap<AccountId, Account>,
 
     //list of pools to diversify in
-    pub staking_pools: Vec<StakingPoolInfo>,
+    pub staking_pools: Vec<OldStakingPoolInfo>,
 
     // validator loan request
     // action on audit suggestions, this field is not used. No need for this to be on the main contract
@@ -174,6 +186,14 @@ impl MetaPool {
             "Can only be called by this contract"
         );
 
+        // stNEAR metadata: keep the value stored by the previous ft_metadata_set, if any, now in contract state
+        let mut legacy_ft_metadata: LazyOption<FungibleTokenMetadata> = LazyOption::new(
+            crate::fungible_token_standard::LEGACY_FT_METADATA_KEY.to_vec(),
+            None,
+        );
+        let ft_metadata_value = legacy_ft_metadata.get().unwrap_or(ft_metadata_default());
+        legacy_ft_metadata.remove();
+
         // Create the new contract state using the data from the old contract state.
         // returns this struct that gets stored as contract state
         return Self {
@@ -182,6 +202,9 @@ impl MetaPool {
             staking_paused: old.staking_paused,
             contract_account_balance: old.contract_account_balance,
             reserve_for_unstake_claims: old.reserve_for_unstake_claims,
+            // claims made before the migration are not tracked, they're considered due (conservative)
+            unstake_claims_by_unlock_epoch: Vec::new(),
+            quick_exit_fee_bp_per_epoch: DEFAULT_QUICK_EXIT_FEE_BP_PER_EPOCH,
             total_available: old.total_available,
 
             //-- ORDERS
@@ -200,8 +223,29 @@ impl MetaPool {
             accumulated_staked_rewards: old.accumulated_staked_rewards,
 
             accounts: old.accounts,
-
-            staking_pools: old.staking_pools,
+            unstake_claims: LookupMap::new(b"U".to_vec()),
+            unstake_tickets: UnorderedMap::new(b"T".to_vec()),
+            unstake_tickets_per_owner: LookupMap::new(b"W".to_vec()),
+            unstake_ticket_last_id: 0,
+
+            staking_pools: old
+                .staking_pools
+                .into_iter()
+                .map(|sp| StakingPoolInfo {
+                    account_id: sp.account_id,
+                    weight_basis_points: sp.weight_basis_points,
+                    busy_lock: sp.busy_lock,
+                    busy_lock_since: env::block_index(),
+                    staked: sp.staked,
+                    unstaked: sp.unstaked,
+                    unstk_req_epoch_height: sp.unstk_req_epoch_height,
+                    last_asked_rewards_epoch_height: sp.last_asked_rewards_epoch_height,
+                    evacuation_amount: 0,
+                    pending_op: PendingOp::None,
+                    pending_op_since: 0,
+                    needs_resync: false,
+                })
+                .collect(),
 
             loan_requests: old.loan_requests,
 
@@ -219,14 +263,39 @@ impl MetaPool {
 
             treasury_account_id: old.treasury_account_id,
             treasury_swap_cut_basis_points: old.treasury_swap_cut_basis_points,
+            insurance_swap_cut_basis_points: 0,
+            insurance_rewards_fee_basis_points: 0,
+            loss_coverage_cap: 0,
 
             // Configurable info for [NEP-129](https://github.com/nearprotocol/NEPs/pull/129)
             web_app_url: old.web_app_url,
             auditor_account_id: old.auditor_account_id,
 
             meta_token_account_id: old.meta_token_account_id,
+            ft_metadata: LazyOption::new(b"M".to_vec(), Some(&ft_metadata_value)),
+            staking_pool_allowed_accounts: default_staking_pool_allowed_accounts(),
+            whitelist_account_id: None,
+            wnear_contract_id: None,
             min_deposit_amount: old.min_deposit_amount,
 
+            price_history: Vector::new(b"H".to_vec()),
+            price_history_last_inx: 0,
+            sp_performance: LookupMap::new(b"P".to_vec()),
+            auto_weights_enabled: false,
+            auto_weights_min_pools: DEFAULT_AUTO_WEIGHTS_MIN_POOLS,
+            auto_weights_last_epoch: 0,
+            sp_weight_overrides: Vec::new(),
+            max_sp_fee_basis_points: DEFAULT_MAX_SP_FEE_BASIS_POINTS,
+            rebalance_max_bp_per_epoch: DEFAULT_REBALANCE_MAX_BP_PER_EPOCH,
+            rebalance_epoch: 0,
+            rebalance_unstaked_this_epoch: 0,
+            max_parallel_pools: 1,
+            contract_busy_since: 0,
+            busy_lock_expiry_blocks: DEFAULT_BUSY_LOCK_EXPIRY_BLOCKS,
+            keeper_bounty: 0,
+            keeper_bounty_max_per_epoch: 0,
+            keeper_bounty_epoch: 0,
+            keeper_bounty_paid_this_epoch: 0,
             est_meta_rewards_stakers: old.est_meta_rewards_stakers,
             est_meta_rewards_lu: old.est_meta_rewards_lu,
             est_meta_rewards_lp: old.est_meta_rewards_lp,
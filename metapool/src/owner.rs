@@ -1,5 +1,5 @@
 use crate::*;
-use near_sdk::{near_bindgen, Promise, PublicKey};
+use near_sdk::{near_bindgen, Promise, PromiseOrValue, PublicKey};
 
 #[near_bindgen]
 impl MetaPool {
@@ -45,6 +45,7 @@ impl MetaPool {
         let mut result = Vec::with_capacity(self.staking_pools.len());
         for inx in 0..self.staking_pools.len() {
             let elem = &self.staking_pools[inx];
+            let perf = self.sp_performance.get(&elem.account_id).unwrap_or_default();
             result.push(StakingPoolJSONInfo {
                 inx: inx as u16,
                 account_id: elem.account_id.clone(),
@@ -54,6 +55,10 @@ impl MetaPool {
                 last_asked_rewards_epoch_height: elem.last_asked_rewards_epoch_height.into(),
                 unstaked_requested_epoch_height: elem.unstk_req_epoch_height.into(),
                 busy_lock: elem.busy_lock,
+                needs_resync: elem.needs_resync,
+                total_rewards: perf.total_rewards.into(),
+                epochs_missed: perf.epochs_missed,
+                reward_rate_bp: perf.history.last().map_or(0, |record| record.reward_rate_bp()),
             })
         }
         return result;
@@ -67,26 +72,217 @@ impl MetaPool {
         if !sp.is_empty() {
             panic!("sp is not empty")
         }
+        let account_id = sp.account_id.clone();
         self.staking_pools.remove(inx as usize);
+        self.sp_weight_overrides.retain(|item| item.0 != account_id);
     }
 
     /// add a new staking pool, checking that it is not already in the list
     /// added with weight_basis_points = 0, to preserve sum(weights)=100%
-    pub fn add_staking_pool(&mut self, account_id: AccountId) {
+    /// if a whitelist contract is configured, the pool is added only if it is whitelisted there (async, see on_whitelist_is_whitelisted)
+    pub fn add_staking_pool(&mut self, account_id: AccountId) -> PromiseOrValue<bool> {
         self.assert_operator_or_owner();
         assert!(
-            account_id.ends_with(".poolv1.near") || account_id.ends_with(".testnet"),
-            "invalid staking-pool contract account {}", account_id);
-        // search the pools
-        for sp_inx in 0..self.staking_pools.len() {
-            if self.staking_pools[sp_inx].account_id == account_id {
-                // found
-                panic!("already in list");
+            self.is_staking_pool_account_allowed(&account_id),
+            "invalid staking-pool contract account {}. See get_staking_pool_allowed_accounts", account_id);
+        assert!(!self.is_staking_pool_in_list(&account_id), "already in list");
+
+        match &self.whitelist_account_id {
+            None => {
+                // no whitelist configured (testnet/sandbox), add
+                self.staking_pools.push(StakingPoolInfo::new(account_id, 0));
+                PromiseOrValue::Value(true)
             }
+            Some(whitelist_account_id) => ext_whitelist::is_whitelisted(
+                account_id.clone(),
+                //promise params:
+                whitelist_account_id,
+                NO_DEPOSIT,
+                gas::whitelist::IS_WHITELISTED,
+            )
+            .then(ext_self_owner::on_whitelist_is_whitelisted(
+                account_id,
+                //promise params:
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                gas::owner_callbacks::ON_WHITELIST_IS_WHITELISTED,
+            ))
+            .into(),
+        }
+    }
+    /// prev fn continues here, after asking the whitelist contract
+    /// adds the pool only if it is whitelisted
+    pub fn on_whitelist_is_whitelisted(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        #[callback] is_whitelisted: bool,
+    ) -> bool {
+        assert_callback_calling();
+        if !is_whitelisted {
+            log!("@{} is not whitelisted, not added", staking_pool_account_id);
+            return false;
+        }
+        // check again, the list could have changed while we were waiting
+        if self.is_staking_pool_in_list(&staking_pool_account_id) {
+            log!("@{} already in list", staking_pool_account_id);
+            return false;
         }
-        // not in list, add
         self.staking_pools
-            .push(StakingPoolInfo::new(account_id, 0));
+            .push(StakingPoolInfo::new(staking_pool_account_id, 0));
+        return true;
+    }
+
+    /// allowed staking-pool factory suffixes (starting with ".", e.g. ".poolv1.near") or exact accounts
+    pub fn get_staking_pool_allowed_accounts(&self) -> Vec<String> {
+        return self.staking_pool_allowed_accounts.clone();
+    }
+    /// owner method. add a factory suffix (starting with ".", e.g. ".pool.near") or an exact staking-pool account
+    pub fn add_staking_pool_allowed_account(&mut self, suffix_or_account: String) {
+        self.assert_owner_calling();
+        let without_dot = suffix_or_account.trim_start_matches('.');
+        assert!(
+            env::is_valid_account_id(without_dot.as_bytes()),
+            "invalid suffix or account {}",
+            suffix_or_account
+        );
+        assert!(
+            !self.staking_pool_allowed_accounts.contains(&suffix_or_account),
+            "already in list"
+        );
+        self.staking_pool_allowed_accounts.push(suffix_or_account);
+    }
+    /// owner method. Pools already added are not affected
+    pub fn remove_staking_pool_allowed_account(&mut self, suffix_or_account: String) {
+        self.assert_owner_calling();
+        let len = self.staking_pool_allowed_accounts.len();
+        self.staking_pool_allowed_accounts
+            .retain(|item| item != &suffix_or_account);
+        assert!(self.staking_pool_allowed_accounts.len() < len, "not in list");
+    }
+
+    pub fn get_loss_coverage_cap(&self) -> U128String {
+        return self.loss_coverage_cap.into();
+    }
+    /// owner method. max NEAR covered by burning insurance fund stNEAR on each staking-pool loss. 0 = disabled
+    pub fn set_loss_coverage_cap(&mut self, amount: U128String) {
+        self.assert_owner_calling();
+        self.loss_coverage_cap = amount.0;
+    }
+
+    pub fn get_max_sp_fee_basis_points(&self) -> u16 {
+        return self.max_sp_fee_basis_points;
+    }
+    /// owner method. check_pool_fee sets weight 0 to pools with a validator fee above this
+    pub fn set_max_sp_fee_basis_points(&mut self, basis_points: u16) {
+        self.assert_owner_calling();
+        assert!(basis_points <= 10000, "invalid basis points");
+        self.max_sp_fee_basis_points = basis_points;
+    }
+
+    pub fn get_rebalance_max_bp_per_epoch(&self) -> u16 {
+        return self.rebalance_max_bp_per_epoch;
+    }
+    /// owner method. max % of the total staked rebalance_unstake can move per epoch. 0 = disabled
+    pub fn set_rebalance_max_bp_per_epoch(&mut self, basis_points: u16) {
+        self.assert_owner_calling();
+        assert!(basis_points <= 10000, "invalid basis points");
+        self.rebalance_max_bp_per_epoch = basis_points;
+    }
+
+    pub fn get_max_parallel_pools(&self) -> u16 {
+        return self.max_parallel_pools;
+    }
+    /// owner method. distribute_staking/unstaking parallel mode if > 1
+    pub fn set_max_parallel_pools(&mut self, max_pools: u16) {
+        self.assert_owner_calling();
+        assert!(
+            max_pools >= 1 && max_pools <= MAX_PARALLEL_POOLS,
+            "max_pools must be in 1..={}",
+            MAX_PARALLEL_POOLS
+        );
+        self.max_parallel_pools = max_pools;
+    }
+
+    pub fn get_busy_lock_expiry_blocks(&self) -> U64String {
+        return self.busy_lock_expiry_blocks.into();
+    }
+    /// owner method. busy locks older than this are considered released
+    pub fn set_busy_lock_expiry_blocks(&mut self, blocks: U64String) {
+        self.assert_owner_calling();
+        assert!(
+            blocks.0 >= MIN_BUSY_LOCK_EXPIRY_BLOCKS,
+            "min is {} blocks",
+            MIN_BUSY_LOCK_EXPIRY_BLOCKS
+        );
+        self.busy_lock_expiry_blocks = blocks.0;
+    }
+
+    pub fn get_keeper_bounty_config(&self) -> KeeperBountyJSON {
+        let epoch = env::epoch_height();
+        return KeeperBountyJSON {
+            bounty: self.keeper_bounty.into(),
+            max_per_epoch: self.keeper_bounty_max_per_epoch.into(),
+            paid_this_epoch: if self.keeper_bounty_epoch == epoch {
+                self.keeper_bounty_paid_this_epoch.into()
+            } else {
+                0.into()
+            },
+        };
+    }
+    /// owner method. bounty paid per pool staked, unstaked, rewarded or retrieved by a heartbeat call, once it succeeded. bounty=0 disables
+    pub fn set_keeper_bounty(&mut self, bounty: U128String, max_per_epoch: U128String) {
+        self.assert_owner_calling();
+        assert!(bounty.0 <= max_per_epoch.0, "bounty > max_per_epoch");
+        self.keeper_bounty = bounty.0;
+        self.keeper_bounty_max_per_epoch = max_per_epoch.0;
+    }
+
+    //---------------------------------
+    // insurance fund
+    //---------------------------------
+    pub fn get_insurance_swap_cut_basis_points(&self) -> u16 {
+        return self.insurance_swap_cut_basis_points;
+    }
+    /// owner method. part of the treasury cut on Liquid Unstake that goes to the insurance fund
+    pub fn set_insurance_swap_cut_basis_points(&mut self, basis_points: u16) {
+        self.assert_owner_calling();
+        assert!(basis_points <= 10_000, "invalid basis points");
+        self.insurance_swap_cut_basis_points = basis_points;
+    }
+    pub fn get_insurance_rewards_fee_basis_points(&self) -> u16 {
+        return self.insurance_rewards_fee_basis_points;
+    }
+    /// owner method. insurance fund fee on staking rewards, max 10%
+    pub fn set_insurance_rewards_fee_basis_points(&mut self, basis_points: u16) {
+        self.assert_owner_calling();
+        assert!(basis_points <= 1_000, "max 10%");
+        self.insurance_rewards_fee_basis_points = basis_points;
+    }
+    /// owner method (governance). Draw stNEAR from the insurance fund, e.g. to compensate users
+    pub fn insurance_transfer(&mut self, receiver_id: AccountId, st_near_amount: U128String) {
+        self.assert_owner_calling();
+        assert!(env::is_valid_account_id(receiver_id.as_bytes()));
+        self.internal_st_near_transfer(
+            &INSURANCE_INTERNAL_ACCOUNT.into(),
+            &receiver_id,
+            st_near_amount.0,
+        );
+        event!("insurance_transfer", {
+            "receiver_id": receiver_id,
+            "stnear": st_near_amount.0.to_string(),
+        });
+    }
+
+    pub fn get_whitelist_account_id(&self) -> Option<AccountId> {
+        return self.whitelist_account_id.clone();
+    }
+    /// set the staking-pools whitelist contract (e.g. lockup-whitelist.near), used by add_staking_pool
+    pub fn set_whitelist_account_id(&mut self, account_id: Option<AccountId>) {
+        self.assert_owner_calling();
+        if let Some(id) = &account_id {
+            assert!(env::is_valid_account_id(id.as_bytes()));
+        }
+        self.whitelist_account_id = account_id;
     }
 
     /// update existing staking pools list, field weight_basis_points
@@ -110,7 +306,7 @@ impl MetaPool {
             // if there's a change
             if self.staking_pools[sp_inx].weight_basis_points != bp {
                 // check pool is not busy
-                assert!(!self.staking_pools[sp_inx].busy_lock,"sp {} is busy",sp_inx);
+                assert!(!self.staking_pools[sp_inx].is_busy(),"sp {} is busy",sp_inx);
                 // set new value
                 self.staking_pools[sp_inx].weight_basis_points = bp;
             }
@@ -149,6 +345,15 @@ impl MetaPool {
         self.treasury_account_id = account_id;
         self.assert_key_accounts_are_different();
     }
+    pub fn get_wnear_contract_id(&self) -> Option<AccountId> {
+        return self.wnear_contract_id.clone();
+    }
+    /// set the NEP-141 wNEAR contract accepted by ft_on_transfer to stake
+    pub fn set_wnear_contract_id(&mut self, account_id: AccountId) {
+        assert!(env::is_valid_account_id(account_id.as_bytes()));
+        self.assert_owner_calling();
+        self.wnear_contract_id = Some(account_id);
+    }
     pub fn set_owner_id(&mut self, owner_id: AccountId) {
         assert!(env::is_valid_account_id(owner_id.as_bytes()));
         self.assert_owner_calling();
@@ -169,6 +374,8 @@ impl MetaPool {
     pub fn get_account_info(&self, account_id: AccountId) -> GetAccountInfoResult {
         let acc = self.internal_get_account(&account_id);
         let staked_near = self.amount_from_stake_shares(acc.stake_shares);
+        let epoch = env::epoch_height();
+        let claims = self.internal_get_unstake_claims(&account_id, &acc);
         // trip_rewards = current_stnear + trip_accum_unstakes - trip_accum_stakes - trip_start_stnear;
         // note: trip_start_stnear is OBSOLETE
         // let trip_rewards = (staked_near + acc.trip_accum_unstakes)
@@ -195,6 +402,15 @@ impl MetaPool {
                 .saturating_sub(env::epoch_height())
                 as u16,
             can_withdraw: (env::epoch_height() >= acc.unstaked_requested_unlock_epoch),
+            unstaked_withdrawable: matured_claims_amount(&claims, epoch).into(),
+            unstake_claims: claims
+                .iter()
+                .map(|claim| UnstakeClaimJSON {
+                    amount: claim.amount.into(),
+                    unlock_epoch: claim.unlock_epoch.into(),
+                    can_withdraw: epoch >= claim.unlock_epoch,
+                })
+                .collect(),
             total: (acc.available + staked_near + acc.unstaked).into(),
             // trip-meter
             trip_start: acc.trip_start.into(),
@@ -262,6 +478,8 @@ impl MetaPool {
     /// Returns JSON representation of the contract state
     pub fn get_contract_state(&self) -> GetContractStateResult {
         let nslp_account = self.internal_get_nslp_account();
+        let insurance_account = self.internal_get_account(&INSURANCE_INTERNAL_ACCOUNT.into());
+        let insurance_near_value = self.amount_from_stake_shares(insurance_account.stake_shares);
 
         return GetContractStateResult {
             env_epoch_height: env::epoch_height().into(),
@@ -289,6 +507,16 @@ impl MetaPool {
                 .internal_get_discount_basis_points(nslp_account.available, TEN_NEAR),
             nslp_min_discount_basis_points: self.nslp_min_discount_basis_points,
             nslp_max_discount_basis_points: self.nslp_max_discount_basis_points,
+            insurance_stnear_balance: insurance_account.stake_shares.into(),
+            insurance_near_value: insurance_near_value.into(),
+            insurance_coverage_basis_points: if self.total_for_staking == 0 {
+                0
+            } else {
+                std::cmp::min(
+                    proportional(10_000, insurance_near_value, self.total_for_staking),
+                    10_000,
+                ) as u16
+            },
             min_deposit_amount: self.min_deposit_amount.into(),
             est_meta_rewards_stakers: self.est_meta_rewards_stakers.into(),
             est_meta_rewards_lu: self.est_meta_rewards_lu.into(), //liquid-unstakers
@@ -362,6 +590,7 @@ impl MetaPool {
     pub fn get_sp_info(&self, inx: u16) -> StakingPoolJSONInfo {
         assert!((inx as usize) < self.staking_pools.len());
         let sp = &self.staking_pools[inx as usize];
+        let perf = self.sp_performance.get(&sp.account_id).unwrap_or_default();
 
         return StakingPoolJSONInfo {
             inx,
@@ -372,6 +601,10 @@ impl MetaPool {
             unstaked_requested_epoch_height: sp.unstk_req_epoch_height.into(),
             last_asked_rewards_epoch_height: sp.last_asked_rewards_epoch_height.into(),
             busy_lock: sp.busy_lock,
+            needs_resync: sp.needs_resync,
+            total_rewards: perf.total_rewards.into(),
+            epochs_missed: perf.epochs_missed,
+            reward_rate_bp: perf.history.last().map_or(0, |record| record.reward_rate_bp()),
         };
     }
 }
@@ -0,0 +1,112 @@
+//! stNEAR price history: one snapshot per epoch in a ring buffer (PRICE_HISTORY_MAX_LEN)
+//! updated when rewards are recorded (on_get_sp_total_balance). Used to compute APYs on-chain
+use crate::*;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::EpochHeight;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct PriceSnapshot {
+    pub epoch: EpochHeight,
+    /// how many NEAR is 1 stNEAR worth, at the end of the snapshot
+    pub st_near_price: u128,
+    pub total_for_staking: u128,
+    /// rewards recorded on this epoch, sum of all pools
+    pub rewards: u128,
+}
+
+impl MetaPool {
+    /// updates this epoch's snapshot or adds a new one, overwriting the oldest when the buffer is full
+    /// WARN: called from a callback, SHOULD NOT PANIC
+    pub(crate) fn internal_record_price_snapshot(&mut self, rewards: u128) {
+        let epoch = env::epoch_height();
+        let st_near_price = self.amount_from_stake_shares(ONE_E24);
+        if let Some(mut last) = self.internal_get_last_price_snapshot() {
+            if last.epoch == epoch {
+                // same epoch, another pool
+                last.st_near_price = st_near_price;
+                last.total_for_staking = self.total_for_staking;
+                last.rewards += rewards;
+                self.price_history.replace(self.price_history_last_inx, &last);
+                return;
+            }
+        }
+        let snapshot = PriceSnapshot {
+            epoch,
+            st_near_price,
+            total_for_staking: self.total_for_staking,
+            rewards,
+        };
+        if self.price_history.len() < PRICE_HISTORY_MAX_LEN {
+            self.price_history.push(&snapshot);
+            self.price_history_last_inx = self.price_history.len() - 1;
+        } else {
+            self.price_history_last_inx = (self.price_history_last_inx + 1) % PRICE_HISTORY_MAX_LEN;
+            self.price_history.replace(self.price_history_last_inx, &snapshot);
+        }
+    }
+
+    fn internal_get_last_price_snapshot(&self) -> Option<PriceSnapshot> {
+        return self.price_history.get(self.price_history_last_inx);
+    }
+
+    /// snapshots from oldest to newest
+    fn internal_price_history(&self) -> Vec<PriceSnapshot> {
+        let len = self.price_history.len();
+        // when the buffer is full, the oldest is the one after the last
+        let start = if len < PRICE_HISTORY_MAX_LEN {
+            0
+        } else {
+            self.price_history_last_inx + 1
+        };
+        return (0..len)
+            .map(|i| self.price_history.get((start + i) % len).unwrap())
+            .collect();
+    }
+
+    /// annualized yield (in basis points) from the price change over the last `epochs` epochs
+    /// uses the newest snapshot at least `epochs` old. None if there's not enough history
+    fn internal_apy_basis_points(&self, history: &Vec<PriceSnapshot>, epochs: u64) -> Option<u32> {
+        let last = history.last()?;
+        let from = history
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.epoch + epochs <= last.epoch)?;
+        if from.st_near_price == 0 || last.st_near_price < from.st_near_price {
+            return Some(0);
+        }
+        let elapsed = (last.epoch - from.epoch) as u128;
+        let increase_bp = proportional(
+            10_000,
+            last.st_near_price - from.st_near_price,
+            from.st_near_price,
+        );
+        return Some((increase_bp * EPOCHS_PER_YEAR as u128 / elapsed) as u32);
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    /// stNEAR price history, oldest first
+    pub fn get_st_near_price_history(&self) -> Vec<PriceSnapshotJSON> {
+        return self
+            .internal_price_history()
+            .iter()
+            .map(|snapshot| PriceSnapshotJSON {
+                epoch: snapshot.epoch.into(),
+                st_near_price: snapshot.st_near_price.into(),
+                total_for_staking: snapshot.total_for_staking.into(),
+                rewards: snapshot.rewards.into(),
+            })
+            .collect();
+    }
+
+    /// annualized yield over the last 7, 30 & 90 epochs, in basis points (simple, not compounded)
+    pub fn get_apy(&self) -> ApyJSON {
+        let history = self.internal_price_history();
+        return ApyJSON {
+            apy_7_epochs_bp: self.internal_apy_basis_points(&history, 7),
+            apy_30_epochs_bp: self.internal_apy_basis_points(&history, 30),
+            apy_90_epochs_bp: self.internal_apy_basis_points(&history, 90),
+        };
+    }
+}
@@ -0,0 +1,188 @@
+//! Quick-exit: a delayed-unstake claim still in the waiting period can be withdrawn early
+//! against the part of `reserve_for_unstake_claims` not needed by the claims maturing before
+//! the unstaking funds are back from the pools. The fee shrinks as maturity gets closer and goes to the NSLP
+use crate::*;
+
+/****************************/
+/* quick-exit internal      */
+/****************************/
+impl MetaPool {
+    /// register a new claim (or ticket) unlocking at unlock_epoch
+    pub(crate) fn internal_track_unstake_claim(&mut self, unlock_epoch: EpochHeight, amount: u128) {
+        self.internal_prune_unstake_claims_by_epoch();
+        for entry in self.unstake_claims_by_unlock_epoch.iter_mut() {
+            if entry.0 == unlock_epoch {
+                entry.1 += amount;
+                return;
+            }
+        }
+        self.unstake_claims_by_unlock_epoch.push((unlock_epoch, amount));
+    }
+
+    /// a claim unlocking at unlock_epoch was paid before maturity
+    pub(crate) fn internal_untrack_unstake_claim(&mut self, unlock_epoch: EpochHeight, amount: u128) {
+        for entry in self.unstake_claims_by_unlock_epoch.iter_mut() {
+            if entry.0 == unlock_epoch {
+                entry.1 = entry.1.saturating_sub(amount);
+            }
+        }
+        self.internal_prune_unstake_claims_by_epoch();
+    }
+
+    /// matured claims are not tracked, they're already due
+    fn internal_prune_unstake_claims_by_epoch(&mut self) {
+        let epoch = env::epoch_height();
+        self.unstake_claims_by_unlock_epoch
+            .retain(|entry| entry.0 > epoch && entry.1 > 0);
+    }
+
+    /// the part of reserve_for_unstake_claims that is not needed to fulfill the claims maturing
+    /// before all the funds being unstaked (waiting in the pools & this epoch unstake orders) are back
+    /// quick-exits can use it without breaking total_unstake_claims == reserve_for_unstake_claims + total_unstaked_and_waiting
+    pub(crate) fn internal_free_reserve_for_quick_exit(&self) -> u128 {
+        let epoch = env::epoch_height();
+        // epoch when all the funds being unstaked are back in the contract
+        let mut funds_back_epoch = epoch;
+        for sp in self.staking_pools.iter() {
+            if sp.unstaked > 0 {
+                funds_back_epoch = std::cmp::max(
+                    funds_back_epoch,
+                    sp.unstk_req_epoch_height + NUM_EPOCHS_TO_UNLOCK,
+                );
+            }
+        }
+        if self.epoch_unstake_orders > 0 {
+            funds_back_epoch = std::cmp::max(
+                funds_back_epoch,
+                epoch + self.internal_compute_current_unstaking_delay(self.epoch_unstake_orders),
+            );
+        }
+        // the claims maturing later will be covered by the funds coming back
+        let maturing_later: u128 = self
+            .unstake_claims_by_unlock_epoch
+            .iter()
+            .filter(|entry| entry.0 > funds_back_epoch)
+            .map(|entry| entry.1)
+            .sum();
+        // everything else (incl. untracked & matured claims) must be covered by the reserve
+        let needed = self.total_unstake_claims.saturating_sub(maturing_later);
+        return self.reserve_for_unstake_claims.saturating_sub(needed);
+    }
+
+    /// fee for the claim parts taken early
+    pub(crate) fn internal_quick_exit_fee(&self, parts: &Vec<UnstakeClaim>, epoch: EpochHeight) -> u128 {
+        let mut fee: u128 = 0;
+        for part in parts.iter() {
+            fee += apply_pct(self.quick_exit_fee_basis_points(part.unlock_epoch - epoch), part.amount);
+        }
+        return fee;
+    }
+
+    /// fee shrinks linearly as the claim maturity gets closer
+    fn quick_exit_fee_basis_points(&self, epochs_to_unlock: EpochHeight) -> u16 {
+        return std::cmp::min(
+            epochs_to_unlock * self.quick_exit_fee_bp_per_epoch as u64,
+            MAX_QUICK_EXIT_FEE_BP as u64,
+        ) as u16;
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    /// user method
+    /// withdraws `amount` from the unstake claims still in the waiting period, nearest unlock first.
+    /// the NEAR is sent to the user minus the quick-exit fee, which goes to the NSLP
+    pub fn quick_exit(&mut self, amount: U128String) -> QuickExitResult {
+        self.assert_not_busy();
+
+        let amount = amount.0;
+        assert!(amount > 0, "amount must be positive");
+        let free_reserve = self.internal_free_reserve_for_quick_exit();
+        assert!(
+            amount <= free_reserve,
+            "Not enough free reserve for a quick-exit. Max amount now: {}",
+            free_reserve
+        );
+
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get_account(&account_id);
+        let mut claims = self.internal_get_unstake_claims(&account_id, &acc);
+        let epoch = env::epoch_height();
+        let parts = take_immature_claims(&mut claims, amount, epoch);
+        let fee = self.internal_quick_exit_fee(&parts, epoch);
+        for part in parts.iter() {
+            self.internal_untrack_unstake_claim(part.unlock_epoch, part.amount);
+        }
+
+        //the claim is fulfilled now, from the reserve
+        assert!(acc.unstaked >= amount, "inconsistency: unstaked < claims");
+        acc.unstaked -= amount;
+        self.reserve_for_unstake_claims -= amount;
+        assert!(self.total_unstake_claims >= amount, "ITUC");
+        self.total_unstake_claims -= amount;
+
+        //the fee stays in the contract, in the NSLP
+        let mut nslp_account = self.internal_get_nslp_account();
+        nslp_account.available += fee;
+        self.total_available += fee;
+        self.internal_save_nslp_account(&nslp_account);
+
+        //--SAVE ACCOUNT--
+        self.internal_update_account(&account_id, &acc);
+        self.internal_save_unstake_claims(&account_id, &claims);
+
+        let near_to_receive = amount - fee;
+        self.native_transfer_to_predecessor(near_to_receive);
+
+        event!("quick_exit", {
+            "account_id": account_id,
+            "amount": amount.to_string(),
+            "fee": fee.to_string(),
+        });
+
+        return QuickExitResult {
+            near: near_to_receive.into(),
+            fee: fee.into(),
+        };
+    }
+
+    //---------------------------------
+    // views
+    //---------------------------------
+
+    /// how much of reserve_for_unstake_claims is free to be used for quick-exits now
+    pub fn get_quick_exit_free_reserve(&self) -> U128String {
+        return self.internal_free_reserve_for_quick_exit().into();
+    }
+
+    /// what account_id would receive if calling quick_exit(amount) now
+    /// panics like quick_exit if amount is over the free reserve
+    pub fn get_quick_exit_quote(&self, account_id: AccountId, amount: U128String) -> QuickExitResult {
+        let free_reserve = self.internal_free_reserve_for_quick_exit();
+        assert!(
+            amount.0 <= free_reserve,
+            "Not enough free reserve for a quick-exit. Max amount now: {}",
+            free_reserve
+        );
+        let acc = self.internal_get_account(&account_id);
+        let mut claims = self.internal_get_unstake_claims(&account_id, &acc);
+        let epoch = env::epoch_height();
+        let parts = take_immature_claims(&mut claims, amount.0, epoch);
+        let fee = self.internal_quick_exit_fee(&parts, epoch);
+        return QuickExitResult {
+            near: (amount.0 - fee).into(),
+            fee: fee.into(),
+        };
+    }
+
+    pub fn get_quick_exit_fee_bp_per_epoch(&self) -> u16 {
+        return self.quick_exit_fee_bp_per_epoch;
+    }
+
+    /// owner method
+    pub fn set_quick_exit_fee_bp_per_epoch(&mut self, basis_points: u16) {
+        self.assert_owner_calling();
+        assert!(basis_points <= MAX_QUICK_EXIT_FEE_BP, "fee too high");
+        self.quick_exit_fee_bp_per_epoch = basis_points;
+    }
+}
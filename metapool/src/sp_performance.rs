@@ -0,0 +1,110 @@
+//! Per staking-pool performance: rewards reported on each on_get_sp_total_balance,
+//! kept by pool account_id (survives pool index changes). The last SP_PERFORMANCE_HISTORY_LEN records are kept
+use crate::*;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::EpochHeight;
+
+/// rewards reported by a pool at `epoch`, for the `epochs` elapsed since the previous report
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct SpRewardsRecord {
+    pub epoch: EpochHeight,
+    pub epochs: u32,
+    /// staked in the pool before the rewards
+    pub staked: u128,
+    pub rewards: u128,
+}
+
+impl SpRewardsRecord {
+    /// annualized reward rate relative to the staked amount, in basis points
+    pub fn reward_rate_bp(&self) -> u32 {
+        if self.staked == 0 || self.epochs == 0 {
+            return 0;
+        }
+        return proportional(
+            10_000 * EPOCHS_PER_YEAR as u128,
+            self.rewards,
+            self.staked.saturating_mul(self.epochs as u128),
+        ) as u32;
+    }
+}
+
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct StakingPoolPerformance {
+    pub total_rewards: u128,
+    /// epochs covered by reports with stake in the pool
+    pub epochs_reported: u32,
+    /// epochs covered by reports with stake in the pool but no rewards
+    pub epochs_missed: u32,
+    pub history: Vec<SpRewardsRecord>,
+    /// validator fee observed by check_pool_fee
+    pub fee_basis_points: u16,
+    pub fee_checked_epoch: EpochHeight,
+}
+
+impl MetaPool {
+    /// WARN: called from a callback, SHOULD NOT PANIC
+    pub(crate) fn internal_record_sp_rewards(
+        &mut self,
+        sp_account_id: &AccountId,
+        prev_asked_epoch: EpochHeight,
+        staked: u128,
+        rewards: u128,
+    ) {
+        let epoch = env::epoch_height();
+        let mut perf = self.sp_performance.get(sp_account_id).unwrap_or_default();
+        // a report covers all the epochs since the previous one
+        let epochs = if prev_asked_epoch == 0 || prev_asked_epoch >= epoch {
+            1
+        } else {
+            (epoch - prev_asked_epoch) as u32
+        };
+        perf.total_rewards += rewards;
+        if staked > 0 {
+            perf.epochs_reported += epochs;
+            if rewards == 0 {
+                perf.epochs_missed += epochs;
+            }
+        }
+        perf.history.push(SpRewardsRecord {
+            epoch,
+            epochs,
+            staked,
+            rewards,
+        });
+        if perf.history.len() > SP_PERFORMANCE_HISTORY_LEN {
+            perf.history.remove(0);
+        }
+        self.sp_performance.insert(sp_account_id, &perf);
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    /// rewards history of the sp at inx, records since from_epoch
+    pub fn get_sp_performance(&self, inx: u16, from_epoch: U64String) -> StakingPoolPerformanceJSON {
+        assert!((inx as usize) < self.staking_pools.len());
+        let sp = &self.staking_pools[inx as usize];
+        let perf = self.sp_performance.get(&sp.account_id).unwrap_or_default();
+        return StakingPoolPerformanceJSON {
+            inx,
+            account_id: sp.account_id.clone(),
+            total_rewards: perf.total_rewards.into(),
+            epochs_reported: perf.epochs_reported,
+            epochs_missed: perf.epochs_missed,
+            fee_basis_points: perf.fee_basis_points,
+            fee_checked_epoch: perf.fee_checked_epoch.into(),
+            history: perf
+                .history
+                .iter()
+                .filter(|record| record.epoch >= from_epoch.0)
+                .map(|record| SpRewardsRecordJSON {
+                    epoch: record.epoch.into(),
+                    epochs: record.epochs,
+                    staked: record.staked.into(),
+                    rewards: record.rewards.into(),
+                    reward_rate_bp: record.reward_rate_bp(),
+                })
+                .collect(),
+        };
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
 
 pub use crate::types::*;
 pub use crate::utils::*;
@@ -22,6 +23,8 @@ pub struct StakingPoolInfo {
 
     //if we've made an async call to this pool
     pub busy_lock: bool,
+    //block when busy_lock was set, the lock expires after busy_lock_expiry_blocks
+    pub busy_lock_since: BlockHeight,
 
     //total staked here
     pub staked: u128,
@@ -36,6 +39,18 @@ pub struct StakingPoolInfo {
 
     //EpochHeight where we asked the sp what were our staking rewards
     pub last_asked_rewards_epoch_height: EpochHeight,
+
+    //evacuation (evacuate_pool) or rebalance (rebalance_unstake) in progress: amount unstaked to be re-staked in the other pools.
+    //when retrieved, it goes to epoch_stake_orders instead of reserve_for_unstake_claims
+    pub evacuation_amount: u128,
+
+    //async op with preventive accounting in flight, settled by its callback
+    pub pending_op: PendingOp,
+    //busy_lock_since of the lock taken for pending_op, its callback must present it
+    pub pending_op_since: BlockHeight,
+    //set by clear_stale_locks when the lock expired with an op in flight.
+    //staked/unstaked can't be trusted until resync_pool reads the real balances from the pool
+    pub needs_resync: bool,
 }
 
 impl StakingPoolInfo {
@@ -43,19 +58,50 @@ impl StakingPoolInfo {
         return self.busy_lock == false
             && self.weight_basis_points == 0
             && self.staked == 0
-            && self.unstaked == 0;
+            && self.unstaked == 0
+            && self.evacuation_amount == 0
+            && !self.needs_resync;
     }
     pub fn new(account_id: AccountId, weight_basis_points: u16) -> Self {
         return Self {
             account_id,
             weight_basis_points,
             busy_lock: false,
+            busy_lock_since: 0,
             staked: 0,
             unstaked: 0,
             unstk_req_epoch_height: 0,
             last_asked_rewards_epoch_height: 0,
+            evacuation_amount: 0,
+            pending_op: PendingOp::None,
+            pending_op_since: 0,
+            needs_resync: false,
         };
     }
+    pub fn lock(&mut self) {
+        self.busy_lock = true;
+        self.busy_lock_since = env::block_index();
+    }
+
+    /// releases the lock taken at lock_since. Returns false if the lock is not that one anymore
+    /// (it expired and was released, maybe taken again): a late callback must not touch the pool
+    pub fn unlock(&mut self, lock_since: BlockHeight) -> bool {
+        if !self.busy_lock || self.busy_lock_since != lock_since {
+            return false;
+        }
+        self.busy_lock = false;
+        return true;
+    }
+
+    /// locked by an async call, or waiting for resync_pool
+    pub fn is_busy(&self) -> bool {
+        self.busy_lock || self.needs_resync
+    }
+
+    pub fn lock_expired(&self, expiry_blocks: u64) -> bool {
+        return env::block_index() >= self.busy_lock_since + expiry_blocks;
+    }
+
     pub fn total_balance(&self) -> u128 {
         self.staked + self.unstaked
     }
@@ -71,6 +117,63 @@ impl StakingPoolInfo {
     }
 }
 
+/// why direct_unstake was called, so on_staking_pool_unstake knows what to undo if the unstake fails
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum UnstakeKind {
+    /// users' epoch_unstake_orders (distribute_unstaking, manual unstake)
+    Orders,
+    /// evacuate_pool, with the weight & override to restore if the unstake fails
+    Evacuation {
+        prev_weight_basis_points: u16,
+        prev_override: Option<u16>,
+    },
+    /// rebalance_unstake, at `epoch`. The amount is refunded to the epoch budget if the unstake fails
+    Rebalance { epoch: EpochHeight },
+}
+
+/// async op on a pool whose callback must settle the accounting.
+/// If the lock expires before the callback runs, resync_pool settles it from the real pool balances
+#[derive(BorshDeserialize, BorshSerialize, Clone, PartialEq)]
+pub enum PendingOp {
+    None,
+    /// direct_stake, `included_deposit` false when restaking sp.unstaked
+    Stake { amount: u128, included_deposit: bool },
+    /// direct_unstake
+    Unstake { amount: u128, kind: UnstakeKind },
+    /// retrieve_funds_from_a_pool & retrieve_all_matured
+    Withdraw { amount: u128 },
+}
+
+impl Default for PendingOp {
+    fn default() -> Self {
+        PendingOp::None
+    }
+}
+
+impl PendingOp {
+    pub fn is_none(&self) -> bool {
+        matches!(self, PendingOp::None)
+    }
+}
+
+/// fee the validator takes from the pool rewards (core-contracts/staking-pool)
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardFeeFraction {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl RewardFeeFraction {
+    pub fn basis_points(&self) -> u16 {
+        if self.denominator == 0 || self.numerator >= self.denominator {
+            return 10000;
+        }
+        return (self.numerator as u64 * 10000 / self.denominator as u64) as u16;
+    }
+}
+
 // -------------------
 // Staking Pools Trait
 // -------------------
@@ -82,6 +185,10 @@ pub trait ExtStakingPool {
 
     fn get_account_total_balance(&self, account_id: AccountId) -> U128String;
 
+    fn get_account(&self, account_id: AccountId) -> HumanReadableAccount;
+
+    fn get_reward_fee_fraction(&self) -> RewardFeeFraction;
+
     fn deposit(&mut self);
 
     fn deposit_and_stake(&mut self);
@@ -95,3 +202,11 @@ pub trait ExtStakingPool {
 
     fn unstake_all(&mut self);
 }
+
+// -------------------
+// Staking Pools Whitelist (lockup-whitelist.near)
+// -------------------
+#[ext_contract(ext_whitelist)]
+pub trait ExtWhitelist {
+    fn is_whitelisted(&self, staking_pool_account_id: AccountId) -> bool;
+}
@@ -12,6 +12,8 @@ pub const STNEAR: &str = "stNEAR";
 
 // internal pseudo-account (must be an invalid near-account-id)
 pub const NSLP_INTERNAL_ACCOUNT: &str = "..NSLP..";
+// internal pseudo-account for the insurance fund (stNEAR), covers staking-pool losses
+pub const INSURANCE_INTERNAL_ACCOUNT: &str = "..INSURANCE..";
 
 /// useful constants
 pub const NO_DEPOSIT: u128 = 0;
@@ -38,6 +40,41 @@ pub const TGAS: u64 = 1_000_000_000_000;
 /// If all staking-pools are unstaking, the user might have to wait 2*NUM_EPOCHS_TO_UNLOCK
 pub const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4; //0 for testing in guild-net, 4 for mainnet & testnet;
 
+/// staking-pool factories allowed by default in add_staking_pool
+pub const DEFAULT_STAKING_POOL_ALLOWED_SUFFIXES: [&str; 2] = [".poolv1.near", ".testnet"];
+
+/// stNEAR price history ring buffer size (one snapshot per epoch)
+pub const PRICE_HISTORY_MAX_LEN: u64 = 128;
+/// epochs are ~12hs. Used to annualize yields
+pub const EPOCHS_PER_YEAR: u64 = 730;
+
+/// rewards records kept per staking pool
+pub const SP_PERFORMANCE_HISTORY_LEN: usize = 64;
+
+/// no staking pool can have 50% or more
+pub const MAX_SP_WEIGHT_BASIS_POINTS: u16 = 4999;
+/// default for auto_weights_min_pools
+pub const DEFAULT_AUTO_WEIGHTS_MIN_POOLS: u16 = 3;
+/// default max validator fee, 10%
+pub const DEFAULT_MAX_SP_FEE_BASIS_POINTS: u16 = 1000;
+/// default rebalance_unstake budget, 2% of the total staked per epoch
+pub const DEFAULT_REBALANCE_MAX_BP_PER_EPOCH: u16 = 200;
+/// max pools queried by distribute_rewards_batch, 4 * (query + ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL) + BASE fits in 300 TGas
+pub const MAX_DISTRIBUTE_REWARDS_BATCH: u16 = 4;
+/// max value for max_parallel_pools
+pub const MAX_PARALLEL_POOLS: u16 = 8;
+/// busy locks expire after ~10 minutes
+pub const DEFAULT_BUSY_LOCK_EXPIRY_BLOCKS: u64 = 600;
+pub const MIN_BUSY_LOCK_EXPIRY_BLOCKS: u64 = 100;
+
+/// Max number of pending delayed-unstake claims per account (claims unlocking on the same epoch are merged)
+pub const MAX_UNSTAKE_CLAIMS_PER_ACCOUNT: usize = 16;
+
+/// quick-exit fee for each epoch remaining until the claim unlocks (0.25% per epoch, 1% for a 4-epochs claim)
+pub const DEFAULT_QUICK_EXIT_FEE_BP_PER_EPOCH: u16 = 25;
+/// quick-exit fee cap
+pub const MAX_QUICK_EXIT_FEE_BP: u16 = 500;
+
 /// The contract keeps at least 35 NEAR in the account to avoid being transferred out to cover
 /// contract code storage and some internal state.
 pub const MIN_BALANCE_FOR_STORAGE: u128 = 35_000_000_000_000_000_000_000_000;
@@ -70,6 +107,7 @@ pub type U128String = U128;
 pub type U64String = U64;
 
 pub type EpochHeight = u64;
+pub type BlockHeight = u64;
 
 /// NEP-129 get information about this contract
 /// returns JSON string according to [NEP-129](https://github.com/nearprotocol/NEPs/pull/129)
@@ -140,6 +178,10 @@ pub struct GetAccountInfoResult {
     pub unstake_full_epochs_wait_left: u16,
     ///if env::epoch_height()>=unstaked_requested_unlock_epoch
     pub can_withdraw: bool,
+    /// The pending delayed-unstake claims, each one with its own unlock epoch
+    pub unstake_claims: Vec<UnstakeClaimJSON>,
+    /// sum of the claims that can be withdrawn now (withdraw_unstaked amount)
+    pub unstaked_withdrawable: U128,
     /// total amount the user holds in this contract: account.available + account.staked + current_rewards + account.unstaked
     pub total: U128,
 
@@ -243,6 +285,12 @@ pub struct GetContractStateResult {
     pub nslp_min_discount_basis_points: u16,
     pub nslp_max_discount_basis_points: u16,
 
+    /// insurance fund, stNEAR balance & NEAR value
+    pub insurance_stnear_balance: U128String,
+    pub insurance_near_value: U128String,
+    /// insurance NEAR value relative to total_for_staking
+    pub insurance_coverage_basis_points: u16,
+
     //how many accounts there are
     pub accounts_count: U64,
 
@@ -289,6 +337,16 @@ pub struct ContractParamsJSON {
     pub min_deposit_amount: U128String,
 }
 
+/// Delayed-unstake claim, part of GetAccountInfoResult
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnstakeClaimJSON {
+    pub amount: U128String,
+    pub unlock_epoch: U64String,
+    ///if env::epoch_height()>=unlock_epoch
+    pub can_withdraw: bool,
+}
+
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct RemoveLiquidityResult {
@@ -304,6 +362,78 @@ pub struct LiquidUnstakeResult {
     pub meta: U128String,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SmartUnstakeResult {
+    /// liquid unstake part
+    pub liquid_unstaked_stnear: U128String,
+    pub near: U128String,
+    pub fee: U128String,
+    pub meta: U128String,
+    /// delayed unstake part
+    pub delayed_unstaked_stnear: U128String,
+    pub delayed_unstake_amount: U128String,
+    pub unlock_epoch: U64String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct QuickExitResult {
+    pub near: U128String,
+    pub fee: U128String,
+}
+
+// check_invariants: partial sums, passed back to continue with the next page
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InvariantSumsJSON {
+    pub available: U128String,
+    pub unstaked: U128String,
+    pub stake_shares: U128String,
+    pub unstake_tickets: U128String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InvariantDiscrepancyJSON {
+    pub check: String,
+    pub expected: U128String,
+    pub actual: U128String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InvariantsCheckJSON {
+    /// from_index for the next call
+    pub next_index: u64,
+    /// accounts + unstake tickets
+    pub total_items: u64,
+    /// all the items were walked, sums were compared with the contract totals
+    pub complete: bool,
+    pub sums: InvariantSumsJSON,
+    pub discrepancies: Vec<InvariantDiscrepancyJSON>,
+}
+
+// get_st_near_price_history
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceSnapshotJSON {
+    pub epoch: U64String,
+    pub st_near_price: U128String,
+    pub total_for_staking: U128String,
+    pub rewards: U128String,
+}
+
+// get_apy: annualized yield in basis points, computed from the stNEAR price history
+// null if there's not enough history
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApyJSON {
+    pub apy_7_epochs_bp: Option<u32>,
+    pub apy_30_epochs_bp: Option<u32>,
+    pub apy_90_epochs_bp: Option<u32>,
+}
+
 // get_staking_pool_list returns StakingPoolJSONInfo[]
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -317,6 +447,69 @@ pub struct StakingPoolJSONInfo {
     //EpochHeight where we asked the sp what were our staking rewards
     pub last_asked_rewards_epoch_height: U64String,
     pub busy_lock: bool,
+    //released by clear_stale_locks with an op in flight, call resync_pool
+    pub needs_resync: bool,
+    //performance: rewards received, epochs without rewards, annualized reward rate of the last report
+    pub total_rewards: U128String,
+    pub epochs_missed: u32,
+    pub reward_rate_bp: u32,
+}
+
+// get_sp_performance returns StakingPoolPerformanceJSON
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SpRewardsRecordJSON {
+    pub epoch: U64String,
+    //epochs since the previous report
+    pub epochs: u32,
+    pub staked: U128String,
+    pub rewards: U128String,
+    //annualized, relative to staked
+    pub reward_rate_bp: u32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakingPoolPerformanceJSON {
+    pub inx: u16,
+    pub account_id: String,
+    pub total_rewards: U128String,
+    pub epochs_reported: u32,
+    pub epochs_missed: u32,
+    //validator fee observed by check_pool_fee
+    pub fee_basis_points: u16,
+    pub fee_checked_epoch: U64String,
+    pub history: Vec<SpRewardsRecordJSON>,
+}
+
+// retrieve_all_matured returns RetrieveReportJSON[]
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RetrieveReportJSON {
+    pub inx: u16,
+    pub account_id: String,
+    pub retrieved: U128String,
+    //the unstaked balance query or the withdraw failed
+    pub failed: bool,
+}
+
+// get_keeper_bounty_config
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeeperBountyJSON {
+    pub bounty: U128String,
+    pub max_per_epoch: U128String,
+    pub paid_this_epoch: U128String,
+}
+
+// get_auto_weights_config
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AutoWeightsConfigJSON {
+    pub enabled: bool,
+    pub min_pools: u16,
+    pub last_epoch: U64String,
+    pub overrides: Vec<StakingPoolArgItem>,
 }
 
 /// struct used as parameter for set_staking_pools
@@ -0,0 +1,483 @@
+//! Transferable delayed-unstake tickets
+//! A delayed-unstake claim can be minted as a NEP-171 non-fungible token, instead of staying in the user's account.
+//! Whoever holds the token once the unlock epoch is reached can redeem it against `reserve_for_unstake_claims`
+//! Implements [NEP-171](https://nomicon.io/Standards/NonFungibleToken/Core.html) core,
+//! [NEP-177](https://nomicon.io/Standards/NonFungibleToken/Metadata.html) metadata and
+//! [NEP-181](https://nomicon.io/Standards/NonFungibleToken/Enumeration.html) enumeration
+use crate::*;
+use near_sdk::collections::UnorderedSet;
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{near_bindgen, Gas, PromiseOrValue, PromiseResult};
+
+pub type TokenId = String;
+
+pub const NFT_METADATA_SPEC: &str = "nft-1.0.0";
+
+const GAS_FOR_NFT_TRANSFER_CALL: Gas = 30_000_000_000_000;
+const GAS_FOR_NFT_RESOLVE_TRANSFER: Gas = 11_000_000_000_000;
+const ONE_TGAS: Gas = 1_000_000_000_000;
+
+/// min NEAR amount for an unstake ticket
+pub const MIN_UNSTAKE_TICKET_AMOUNT: u128 = ONE_NEAR;
+/// NEAR to attach when minting an unstake ticket, covers the ticket storage. Returned to the holder on redeem
+pub const UNSTAKE_TICKET_STORAGE_DEPOSIT: u128 = 10 * ONE_MILLI_NEAR;
+
+//-----------------------------
+// Unstake ticket data
+//-----------------------------
+/// A delayed-unstake claim held as a NFT
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UnstakeTicket {
+    pub owner_id: AccountId,
+    /// NEAR amount that can be redeemed at unlock_epoch
+    pub amount: u128,
+    /// The epoch height when the ticket can be redeemed
+    pub unlock_epoch: EpochHeight,
+    /// the epoch height when the ticket was minted
+    pub minted_epoch: EpochHeight,
+    /// NEAR attached on mint for storage, returned with the amount on redeem
+    pub storage_deposit: u128,
+}
+
+/// NEP-177 contract metadata
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NFTContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+/// NEP-177 token metadata
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<String>,
+    pub copies: Option<u64>,
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// JSON with the ticket amount & unlock_epoch
+    pub extra: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+/// NEP-171 token as returned by nft_token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Token {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub metadata: Option<TokenMetadata>,
+}
+
+/// Struct returned from get_unstake_ticket
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnstakeTicketJSON {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub amount: U128String,
+    pub unlock_epoch: U64String,
+    pub minted_epoch: U64String,
+    ///if env::epoch_height()>=unlock_epoch
+    pub can_redeem: bool,
+}
+
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+#[ext_contract(ext_nft_self)]
+trait NonFungibleTokenResolver {
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<Vec<AccountId>>,
+    ) -> bool;
+}
+
+//-----------------------------
+// Internal methods
+//-----------------------------
+impl MetaPool {
+    /// mints a new unstake ticket, returns the token id
+    /// UNSTAKE_TICKET_STORAGE_DEPOSIT is kept from the attached deposit, the excess is refunded
+    pub(crate) fn internal_mint_unstake_ticket(
+        &mut self,
+        owner_id: &AccountId,
+        amount: u128,
+        unlock_epoch: EpochHeight,
+    ) -> TokenId {
+        assert!(
+            amount >= MIN_UNSTAKE_TICKET_AMOUNT,
+            "The min amount for an unstake ticket is {} NEAR",
+            MIN_UNSTAKE_TICKET_AMOUNT / ONE_NEAR
+        );
+        assert!(
+            env::attached_deposit() >= UNSTAKE_TICKET_STORAGE_DEPOSIT,
+            "Attach at least {} yoctoNEAR to cover the ticket storage",
+            UNSTAKE_TICKET_STORAGE_DEPOSIT
+        );
+        let storage_deposit = UNSTAKE_TICKET_STORAGE_DEPOSIT;
+        let refund = env::attached_deposit() - storage_deposit;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        // the deposit is returned on redeem, keep it out of the extra balance
+        self.contract_account_balance += storage_deposit;
+
+        self.unstake_ticket_last_id += 1;
+        let token_id: TokenId = self.unstake_ticket_last_id.to_string();
+        let ticket = UnstakeTicket {
+            owner_id: owner_id.clone(),
+            amount,
+            unlock_epoch,
+            minted_epoch: env::epoch_height(),
+            storage_deposit,
+        };
+        self.unstake_tickets.insert(&token_id, &ticket);
+        self.internal_add_ticket_to_owner(owner_id, &token_id);
+        log_nft_mint(owner_id, &token_id);
+        return token_id;
+    }
+
+    pub(crate) fn internal_get_unstake_ticket(&self, token_id: &TokenId) -> UnstakeTicket {
+        self.unstake_tickets
+            .get(token_id)
+            .expect("unstake ticket not found")
+    }
+
+    fn internal_add_ticket_to_owner(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let mut owner_tickets = self
+            .unstake_tickets_per_owner
+            .get(owner_id)
+            .unwrap_or_else(|| {
+                // one set per owner, storage prefix derived from the owner account
+                let mut prefix = b"O".to_vec();
+                prefix.extend(env::sha256(owner_id.as_bytes()));
+                UnorderedSet::new(prefix)
+            });
+        owner_tickets.insert(token_id);
+        self.unstake_tickets_per_owner.insert(owner_id, &owner_tickets);
+    }
+
+    fn internal_remove_ticket_from_owner(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let mut owner_tickets = self
+            .unstake_tickets_per_owner
+            .get(owner_id)
+            .expect("ticket owner not found");
+        owner_tickets.remove(token_id);
+        if owner_tickets.is_empty() {
+            self.unstake_tickets_per_owner.remove(owner_id);
+        } else {
+            self.unstake_tickets_per_owner.insert(owner_id, &owner_tickets);
+        }
+    }
+
+    /// moves the ticket to receiver_id, returns the ticket previous owner
+    pub(crate) fn internal_nft_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        memo: Option<String>,
+    ) -> AccountId {
+        let mut ticket = self.internal_get_unstake_ticket(token_id);
+        assert_eq!(
+            &ticket.owner_id, sender_id,
+            "Sender is not the ticket owner"
+        );
+        assert_ne!(
+            sender_id, receiver_id,
+            "Sender and receiver should be different"
+        );
+        self.internal_remove_ticket_from_owner(sender_id, token_id);
+        self.internal_add_ticket_to_owner(receiver_id, token_id);
+        ticket.owner_id = receiver_id.clone();
+        self.unstake_tickets.insert(token_id, &ticket);
+
+        log_nft_transfer(sender_id, receiver_id, token_id, memo);
+
+        return sender_id.clone();
+    }
+
+    pub(crate) fn ticket_to_token(&self, token_id: &TokenId, ticket: &UnstakeTicket) -> Token {
+        return Token {
+            token_id: token_id.clone(),
+            owner_id: ticket.owner_id.clone(),
+            metadata: Some(TokenMetadata {
+                title: Some(format!(
+                    "Unstake ticket #{}, {} NEAR at epoch {}",
+                    token_id,
+                    ticket.amount / ONE_NEAR,
+                    ticket.unlock_epoch
+                )),
+                description: Some(
+                    "Meta Pool delayed-unstake claim. Redeem it for NEAR once the unlock epoch is reached".into(),
+                ),
+                media: None,
+                media_hash: None,
+                copies: Some(1),
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: Some(format!(
+                    r#"{{"amount":"{}","unlock_epoch":"{}","minted_epoch":"{}"}}"#,
+                    ticket.amount, ticket.unlock_epoch, ticket.minted_epoch
+                )),
+                reference: None,
+                reference_hash: None,
+            }),
+        };
+    }
+}
+
+#[near_bindgen]
+impl MetaPool {
+    //---------------------------------
+    // NEP-171 core
+    //---------------------------------
+    /// Transfer an unstake ticket from the caller (`predecessor_id`) to `receiver_id`
+    /// approvals are not supported (approval_id must be null)
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        assert!(approval_id.is_none(), "Approvals are not supported");
+        self.internal_nft_transfer(
+            &env::predecessor_account_id(),
+            &receiver_id.into(),
+            &token_id,
+            memo,
+        );
+    }
+
+    /// Transfer an unstake ticket and call `nft_on_transfer` on the receiver contract
+    /// if the receiver returns true, or the call fails, the ticket is returned to the sender
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        assert!(approval_id.is_none(), "Approvals are not supported");
+        assert!(
+            env::prepaid_gas() > GAS_FOR_NFT_TRANSFER_CALL + GAS_FOR_NFT_RESOLVE_TRANSFER,
+            "gas required {}",
+            GAS_FOR_NFT_TRANSFER_CALL + GAS_FOR_NFT_RESOLVE_TRANSFER
+        );
+        let receiver_id: AccountId = receiver_id.into();
+        let previous_owner_id =
+            self.internal_nft_transfer(&env::predecessor_account_id(), &receiver_id, &token_id, memo);
+
+        ext_nft_receiver::nft_on_transfer(
+            env::predecessor_account_id(),
+            previous_owner_id.clone(),
+            token_id.clone(),
+            msg,
+            //promise params:
+            &receiver_id, //contract
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL - GAS_FOR_NFT_RESOLVE_TRANSFER - ONE_TGAS, // set almost all remaining gas for nft_on_transfer
+        )
+        .then(ext_nft_self::nft_resolve_transfer(
+            previous_owner_id,
+            receiver_id,
+            token_id,
+            None,
+            //promise params:
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_NFT_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// prev fn continues here
+    /// returns true if the ticket was successfully transferred to receiver_id
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        #[allow(unused)] approved_account_ids: Option<Vec<AccountId>>,
+    ) -> bool {
+        // the receiver returns true if the ticket should be returned
+        let must_return = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            PromiseResult::Failed => true,
+        };
+        if !must_return {
+            return true;
+        }
+        // the ticket could have been redeemed or transferred by the receiver in the meantime
+        if let Some(ticket) = self.unstake_tickets.get(&token_id) {
+            if ticket.owner_id == receiver_id {
+                self.internal_nft_transfer(&receiver_id, &previous_owner_id, &token_id, None);
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /// Returns the unstake ticket with the given `token_id` or `null` if no such ticket.
+    pub fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.unstake_tickets
+            .get(&token_id)
+            .map(|ticket| self.ticket_to_token(&token_id, &ticket))
+    }
+
+    //---------------------------------
+    // NEP-177 metadata
+    //---------------------------------
+    pub fn nft_metadata(&self) -> NFTContractMetadata {
+        return NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.into(),
+            name: "Meta Pool Unstake Tickets".into(),
+            symbol: "UNSTK".into(),
+            icon: None,
+            base_uri: None,
+            reference: self.web_app_url.clone(),
+            reference_hash: None,
+        };
+    }
+
+    //---------------------------------
+    // NEP-181 enumeration
+    //---------------------------------
+    pub fn nft_total_supply(&self) -> U128 {
+        (self.unstake_tickets.len() as u128).into()
+    }
+
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        let keys = self.unstake_tickets.keys_as_vector();
+        let start = from_index.map(|x| x.0 as u64).unwrap_or(0);
+        let end = std::cmp::min(start + limit.unwrap_or(keys.len()), keys.len());
+        return (start..end)
+            .map(|index| {
+                let token_id = keys.get(index).unwrap();
+                self.ticket_to_token(&token_id, &self.internal_get_unstake_ticket(&token_id))
+            })
+            .collect();
+    }
+
+    pub fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U128 {
+        match self.unstake_tickets_per_owner.get(account_id.as_ref()) {
+            Some(owner_tickets) => (owner_tickets.len() as u128).into(),
+            None => 0.into(),
+        }
+    }
+
+    pub fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        let owner_tickets = match self.unstake_tickets_per_owner.get(account_id.as_ref()) {
+            Some(owner_tickets) => owner_tickets,
+            None => return vec![],
+        };
+        let keys = owner_tickets.as_vector();
+        let start = from_index.map(|x| x.0 as u64).unwrap_or(0);
+        let end = std::cmp::min(start + limit.unwrap_or(keys.len()), keys.len());
+        return (start..end)
+            .map(|index| {
+                let token_id = keys.get(index).unwrap();
+                self.ticket_to_token(&token_id, &self.internal_get_unstake_ticket(&token_id))
+            })
+            .collect();
+    }
+
+    //---------------------------------
+    // meta-pool extensions
+    //---------------------------------
+    /// unstake ticket info
+    pub fn get_unstake_ticket(&self, token_id: TokenId) -> UnstakeTicketJSON {
+        let ticket = self.internal_get_unstake_ticket(&token_id);
+        return UnstakeTicketJSON {
+            token_id,
+            owner_id: ticket.owner_id,
+            amount: ticket.amount.into(),
+            unlock_epoch: ticket.unlock_epoch.into(),
+            minted_epoch: ticket.minted_epoch.into(),
+            can_redeem: env::epoch_height() >= ticket.unlock_epoch,
+        };
+    }
+
+    /// user method
+    /// the ticket holder redeems (burns) a matured unstake ticket, the NEAR (and the storage deposit) is sent to the holder's account
+    pub fn redeem_unstake_ticket(&mut self, token_id: TokenId) -> Promise {
+        self.assert_not_busy();
+
+        let account_id = env::predecessor_account_id();
+        let ticket = self.internal_get_unstake_ticket(&token_id);
+        assert_eq!(ticket.owner_id, account_id, "You're not the ticket owner");
+
+        let epoch = env::epoch_height();
+        assert!(
+            epoch >= ticket.unlock_epoch,
+            "The ticket is not yet redeemable due to unstaking delay. You need to wait at least {} epochs",
+            ticket.unlock_epoch - epoch
+        );
+        //check the heart beat has really moved the funds
+        assert!(
+            self.reserve_for_unstake_claims >= ticket.amount,
+            "Funds are not yet available due to unstaking delay. Epoch:{}",
+            epoch
+        );
+
+        //burn the ticket
+        self.unstake_tickets.remove(&token_id);
+        self.internal_remove_ticket_from_owner(&account_id, &token_id);
+        log_nft_burn(&account_id, &token_id);
+
+        // in the contract, the claim is fulfilled from reserve_for_unstake_claims
+        self.reserve_for_unstake_claims -= ticket.amount;
+        assert!(self.total_unstake_claims >= ticket.amount, "ITUC");
+        self.total_unstake_claims -= ticket.amount;
+
+        event!("redeem_unstake_ticket", {
+            "account_id": account_id,
+            "token_id": token_id,
+            "amount": ticket.amount.to_string(),
+        });
+
+        //transfer to user native near account, storage deposit included
+        return self.native_transfer_to_predecessor(ticket.amount + ticket.storage_deposit);
+    }
+}
@@ -1,11 +1,118 @@
 pub use crate::types::*;
+use near_sdk::serde_json::{json, Value};
 use near_sdk::{env, PromiseResult};
 
+/// [NEP-297](https://nomicon.io/Standards/EventsFormat) standard name & version for metapool events
+pub const EVENT_STANDARD: &str = "metapool";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+/// stNEAR events, [NEP-141 events](https://nomicon.io/Standards/Tokens/FungibleToken/Event)
+pub const FT_EVENT_STANDARD: &str = "nep141";
+pub const FT_EVENT_STANDARD_VERSION: &str = "1.0.0";
+/// unstake ticket events, [NEP-171 events](https://nomicon.io/Standards/Tokens/NonFungibleToken/Event)
+pub const NFT_EVENT_STANDARD: &str = "nep171";
+pub const NFT_EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+/// logs a NEP-297 event: EVENT_JSON:{"standard":..,"version":..,"event":..,"data":[..]}
+pub fn log_nep297_event(standard: &str, version: &str, event: &str, data: Vec<Value>) {
+    let event_json = json!({
+        "standard": standard,
+        "version": version,
+        "event": event,
+        "data": data,
+    });
+    env::log(format!("EVENT_JSON:{}", event_json.to_string()).as_bytes());
+}
+
+/// metapool NEP-297 event. amounts must be strings (u128 are not json numbers)
+/// e.g. event!("stake", {"account_id": account_id, "amount": amount.to_string()})
 #[macro_export]
 macro_rules! event {
-    ($($arg:tt)*) => ({
-        env::log(format!($($arg)*).as_bytes());
+    ($event:expr, $($data:tt)+) => ({
+        crate::utils::log_nep297_event(
+            crate::utils::EVENT_STANDARD,
+            crate::utils::EVENT_STANDARD_VERSION,
+            $event,
+            vec![near_sdk::serde_json::json!($($data)+)],
+        );
+    });
+}
+
+/// NEP-141 ft_mint event for stNEAR
+pub fn log_ft_mint(owner_id: &str, amount: u128) {
+    if amount > 0 {
+        log_nep297_event(
+            FT_EVENT_STANDARD,
+            FT_EVENT_STANDARD_VERSION,
+            "ft_mint",
+            vec![json!({"owner_id": owner_id, "amount": amount.to_string()})],
+        );
+    }
+}
+
+/// NEP-141 ft_burn event for stNEAR
+pub fn log_ft_burn(owner_id: &str, amount: u128) {
+    if amount > 0 {
+        log_nep297_event(
+            FT_EVENT_STANDARD,
+            FT_EVENT_STANDARD_VERSION,
+            "ft_burn",
+            vec![json!({"owner_id": owner_id, "amount": amount.to_string()})],
+        );
+    }
+}
+
+/// NEP-141 ft_transfer event for stNEAR
+pub fn log_ft_transfer(old_owner_id: &str, new_owner_id: &str, amount: u128) {
+    if amount > 0 {
+        log_nep297_event(
+            FT_EVENT_STANDARD,
+            FT_EVENT_STANDARD_VERSION,
+            "ft_transfer",
+            vec![json!({
+                "old_owner_id": old_owner_id,
+                "new_owner_id": new_owner_id,
+                "amount": amount.to_string()
+            })],
+        );
+    }
+}
+
+/// NEP-171 nft_mint event for unstake tickets
+pub fn log_nft_mint(owner_id: &str, token_id: &str) {
+    log_nep297_event(
+        NFT_EVENT_STANDARD,
+        NFT_EVENT_STANDARD_VERSION,
+        "nft_mint",
+        vec![json!({"owner_id": owner_id, "token_ids": [token_id]})],
+    );
+}
+
+/// NEP-171 nft_burn event for unstake tickets
+pub fn log_nft_burn(owner_id: &str, token_id: &str) {
+    log_nep297_event(
+        NFT_EVENT_STANDARD,
+        NFT_EVENT_STANDARD_VERSION,
+        "nft_burn",
+        vec![json!({"owner_id": owner_id, "token_ids": [token_id]})],
+    );
+}
+
+/// NEP-171 nft_transfer event for unstake tickets
+pub fn log_nft_transfer(old_owner_id: &str, new_owner_id: &str, token_id: &str, memo: Option<String>) {
+    let mut data = json!({
+        "old_owner_id": old_owner_id,
+        "new_owner_id": new_owner_id,
+        "token_ids": [token_id],
     });
+    if let Some(memo) = memo {
+        data["memo"] = json!(memo);
+    }
+    log_nep297_event(
+        NFT_EVENT_STANDARD,
+        NFT_EVENT_STANDARD_VERSION,
+        "nft_transfer",
+        vec![data],
+    );
 }
 
 #[macro_export]
@@ -21,6 +128,13 @@ macro_rules! debug {
     
 }
 
+pub fn default_staking_pool_allowed_accounts() -> Vec<String> {
+    DEFAULT_STAKING_POOL_ALLOWED_SUFFIXES
+        .iter()
+        .map(|suffix| String::from(*suffix))
+        .collect()
+}
+
 pub fn assert_min_balance(amount: u128) {
     assert!(amount > 0, "Amount should be positive");
     assert!(
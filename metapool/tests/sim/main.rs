@@ -6,3 +6,13 @@ mod sim_utils;
 //mod simulation_simple; //simple cases
 //mod simulation_desk_check; //desk check
 mod simulation_fuzzy; //fuzzy tests, check invariants after each step
+mod simulation_gas; //gas measurements for the multi-pool callbacks
+mod simulation_unstake_claims; //delayed-unstake claims queue
+mod simulation_unstake_tickets; //delayed-unstake claims as NFTs
+mod simulation_ft_receiver; //ft_transfer_call to the metapool: stake wNEAR, liquid unstake stNEAR
+mod simulation_loss; //staking loss in a pool
+mod simulation_evacuation; //evacuation of a pool
+mod simulation_rebalance; //rebalance of the over-weight pools
+mod simulation_rewards_batch; //rewards of all the pools in one call
+mod simulation_parallel; //parallel staking & unstaking
+mod simulation_retrieve_all; //retrieve_all_matured
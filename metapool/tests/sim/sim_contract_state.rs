@@ -49,6 +49,9 @@ pub struct State {
     pub unstaked_in_pools: u128,
     pub total_in_pools: u128,
 
+    //storage deposits of the unstake tickets, returned on redeem
+    pub unstake_tickets_storage: u128,
+
     pub sps: Vec<Value>,
 }
 
@@ -117,6 +120,8 @@ pub fn build_state(sim: &Simulation) -> State {
 
     let to_stake_delta = total_for_staking as i128 - total_actually_staked as i128;
 
+    let tickets_supply = as_u128(&view!(metapool.nft_total_supply()).unwrap_json_value());
+
     return State {
         epoch: as_u128(&contract_state["env_epoch_height"]) as u64,
 
@@ -146,6 +151,8 @@ pub fn build_state(sim: &Simulation) -> State {
         unstaked_in_pools: sum_unstaked,
         total_in_pools: sum_staked + sum_unstaked,
 
+        unstake_tickets_storage: tickets_supply * UNSTAKE_TICKET_STORAGE_DEPOSIT,
+
         sps,
     };
 }
@@ -207,10 +214,13 @@ impl State {
         }
 
         if self.contract_account_balance
-            != self.total_available + self.reserve_for_withdraw + self.epoch_stake_orders
+            != self.total_available
+                + self.reserve_for_withdraw
+                + self.epoch_stake_orders
+                + self.unstake_tickets_storage
         {
             return Err(
-                "CAB != self.total_available + self.reserve_for_withdraw + self.epoch_stake_orders"
+                "CAB != self.total_available + self.reserve_for_withdraw + self.epoch_stake_orders + self.unstake_tickets_storage"
                     .into(),
             );
         }
@@ -234,3 +244,28 @@ impl State {
         );
     }
 }
+
+/// pages through the on-chain check_invariants view, panics if it reports a discrepancy
+pub fn assert_on_chain_invariants(sim: &Simulation) {
+    let mut from_index: u64 = 0;
+    let mut sums = Value::Null;
+    loop {
+        let result = sim.metapool.user_account.view(
+            sim.metapool.account_id(),
+            "check_invariants",
+            json!({ "from_index": from_index, "limit": 10, "sums": sums })
+                .to_string()
+                .as_bytes(),
+        );
+        let check = result.unwrap_json_value();
+        let discrepancies = check["discrepancies"].as_array().unwrap();
+        if !discrepancies.is_empty() {
+            panic!("check_invariants {}", Value::Array(discrepancies.clone()));
+        }
+        if check["complete"].as_bool().unwrap() {
+            break;
+        }
+        from_index = check["next_index"].as_u64().unwrap();
+        sums = check["sums"].clone();
+    }
+}
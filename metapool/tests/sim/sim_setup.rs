@@ -24,6 +24,9 @@ near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
   // static ref WASM_BYTES_SP: &'static [u8] = include_bytes!("../../res/staking_pool.wasm").as_ref();
   WASM_BYTES_GET_EPOCH => "../res/get_epoch_contract.wasm",
   // static ref WASM_BYTES_GET_EPOCH: &'static [u8] = include_bytes!("../../res/get_epoch_contract.wasm").as_ref();
+  WASM_BYTES_MOCK_SP => "../res/mock_staking_pool.wasm",
+  // wNEAR mock (test-mock-wnear), same interface as near/core-contracts w-near
+  WASM_BYTES_WNEAR => "../res/w_near.wasm",
 }
 
 /// Deploy the contract(s) and create some metapool accounts. Returns:
@@ -242,6 +245,99 @@ impl Simulation {
         };
     }
 
+    pub fn epoch(&self) -> u64 {
+        view(&self.get_epoch_acc, "get_epoch_height", "{}")
+            .as_u64()
+            .unwrap()
+    }
+
+    /// make dummy txns until `epochs` epochs have passed
+    pub fn advance_epochs(&self, epochs: u64) {
+        let target = self.epoch() + epochs;
+        while self.epoch() < target {
+            call(
+                &self.owner,
+                &self.get_epoch_acc,
+                "set_i32",
+                r#"{"num":0}"#,
+                0,
+                10 * TGAS,
+            );
+        }
+        println!("epoch {}", self.epoch());
+    }
+
+    /// deploys the mock staking pool (test-mock-staking-pool) as sp4 and gives it `weight_pct`%,
+    /// taken from sp1 (the pool with the greatest weight). Returns its index
+    pub fn add_mock_staking_pool(&mut self, weight_pct: u16) -> usize {
+        let acc_id = "spmock.testnet";
+        let sp = self.testnet.deploy(&WASM_BYTES_MOCK_SP, acc_id.into(), SP_INITIAL_BALANCE);
+        check_exec_result(&call(
+            &self.testnet,
+            &sp,
+            "new",
+            &format!(
+                r#"{{"owner_id":"{}","reward_fee_fraction":{{"numerator":5,"denominator":100}}}}"#,
+                self.owner.account_id()
+            ),
+            0,
+            50 * TGAS,
+        ));
+        let metapool = &self.metapool;
+        let res = call!(
+            self.owner,
+            metapool.add_staking_pool(acc_id.into()),
+            gas = 25 * TGAS
+        );
+        check_exec_result(&res);
+        self.sp.push(sp);
+        self.weight_basis_points_vec[1] -= weight_pct;
+        self.weight_basis_points_vec.push(weight_pct);
+
+        let mut pools: Vec<StakingPoolArgItem> = Vec::with_capacity(self.sp.len());
+        for n in 0..self.sp.len() {
+            pools.push(StakingPoolArgItem {
+                account_id: self.sp[n].account_id(),
+                weight_basis_points: self.weight_basis_points_vec[n] * 100,
+            });
+        }
+        let res = call!(
+            self.owner,
+            metapool.set_staking_pools(pools),
+            1,
+            125 * TGAS
+        );
+        check_exec_result(&res);
+        return self.sp.len() - 1;
+    }
+
+    /// deploys wNEAR and sets it in the metapool, the metapool & `users` are registered in wNEAR
+    pub fn deploy_wnear(&self, users: &[&UserAccount]) -> UserAccount {
+        let wnear = self.testnet.deploy(&WASM_BYTES_WNEAR, "wrap.testnet".into(), SP_INITIAL_BALANCE);
+        check_exec_result(&call(&self.testnet, &wnear, "new", "{}", 0, 50 * TGAS));
+        let mut accounts = vec![self.metapool.account_id()];
+        for user in users {
+            accounts.push(user.account_id());
+        }
+        for account_id in accounts {
+            check_exec_result(&call(
+                &self.owner,
+                &wnear,
+                "storage_deposit",
+                &format!(r#"{{"account_id":"{}"}}"#, account_id),
+                NEAR / 100,
+                50 * TGAS,
+            ));
+        }
+        let metapool = &self.metapool;
+        check_exec_result(&call!(
+            self.owner,
+            metapool.set_wnear_contract_id(wnear.account_id()),
+            gas = 25 * TGAS
+        ));
+        return wnear;
+    }
+
     pub fn sp_staked(&self, n: usize) -> u128 {
         view_u128(
             &self.sp[n],
@@ -280,3 +280,70 @@ pub fn step_call(
         return Err(msg);
     }
 }
+
+//-----------
+// helpers for the feature scenarios
+//-----------
+
+/// heartbeat: clearing, staking/unstaking and retrieve of all the pools with the waiting period ended
+pub fn bot_heartbeat(sim: &Simulation, start: &State) -> State {
+    let mut state = bot_end_of_epoch_clearing(sim, start).unwrap().state;
+    state = bot_distributes(sim, &state).unwrap().state;
+    state = step_call(
+        sim,
+        &sim.operator,
+        "retrieve_all_matured",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    return state;
+}
+
+/// runs the heartbeat on every epoch until `epoch`
+pub fn run_epochs_until(sim: &Simulation, start: &State, epoch: u64) -> State {
+    let mut state = bot_heartbeat(sim, start);
+    while sim.epoch() < epoch {
+        sim.advance_epochs(1);
+        state = bot_heartbeat(sim, &state);
+    }
+    return state;
+}
+
+/// for calls to other contracts (wNEAR, pools), checks the result and the sim invariants
+pub fn check_call(sim: &Simulation, res: &ExecutionResult) -> State {
+    check_exec_result(res);
+    let state = build_state(sim);
+    if let Err(err) = state.test_invariants() {
+        panic!("invariant check {}", err);
+    }
+    return state;
+}
+
+pub fn stake(sim: &Simulation, user: &UserAccount, amount: u128, state: &State) -> State {
+    let state = step_call(
+        sim,
+        user,
+        "deposit_and_stake",
+        json!({}),
+        50 * TGAS,
+        amount,
+        state,
+    )
+    .unwrap()
+    .state;
+    return bot_distributes(sim, &state).unwrap().state;
+}
+
+pub fn unlock_epochs(sim: &Simulation, account_id: &str) -> Vec<u64> {
+    let info = sim.show_account_info(account_id);
+    return info["unstake_claims"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|claim| as_u128(&claim["unlock_epoch"]) as u64)
+        .collect();
+}
@@ -201,6 +201,15 @@ pub fn assert_less_than_one_milli_near_diff_balance(
     );
 }
 
+/// true if any receipt of the transaction logged the metapool event `event`
+pub fn logged_event(res: &ExecutionResult, event: &str) -> bool {
+    let pattern = format!(r#""event":"{}""#, event);
+    res.promise_results().iter().any(|item| match item {
+        Some(pr) => pr.outcome().logs.iter().any(|line| line.contains(&pattern)),
+        None => false,
+    })
+}
+
 pub fn balance(acc: &UserAccount) -> u128 {
     if let Some(data) = acc.account() {
         data.amount + data.locked
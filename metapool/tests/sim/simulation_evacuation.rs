@@ -0,0 +1,55 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// evacuation of a pool: unstake all, its weight spread on the other pools, re-staked once retrieved
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_evacuate_pool() {
+    let sim = Simulation::new();
+    let user = sim.testnet.create_user("staker".into(), ntoy(500_000));
+    let mut state = stake(&sim, &user, 100_000 * NEAR, &build_state(&sim));
+    let sum_weights = |state: &State| -> u64 {
+        state.sps.iter().map(|sp| sp["weight_basis_points"].as_u64().unwrap()).sum()
+    };
+
+    // evacuation of sp0
+    let evacuated = as_u128(&state.sps[0]["staked"]);
+    assert!(evacuated > 0);
+    state = step_call(
+        &sim,
+        &sim.operator,
+        "evacuate_pool",
+        json!({ "inx": 0 }),
+        200 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    // its weight is spread on the other pools
+    assert_eq!(state.sps[0]["weight_basis_points"].as_u64().unwrap(), 0);
+    assert_eq!(sum_weights(&state), 10_000);
+    assert_eq!(as_u128(&state.sps[0]["unstaked"]), evacuated);
+    assert_on_chain_invariants(&sim);
+
+    // once retrieved, the funds are re-staked in the other pools
+    let unlock_epoch = sim.epoch() + NUM_EPOCHS_TO_UNLOCK;
+    state = run_epochs_until(&sim, &state, unlock_epoch);
+    state = bot_heartbeat(&sim, &state);
+    assert_eq!(as_u128(&state.sps[0]["staked"]), 0);
+    assert_eq!(as_u128(&state.sps[0]["unstaked"]), 0);
+    assert_eq!(state.epoch_stake_orders, 0);
+    assert_eq!(state.total_actually_staked, state.total_for_staking);
+    assert_eq!(state.staked_in_pools, state.total_actually_staked);
+    assert_on_chain_invariants(&sim);
+}
@@ -0,0 +1,137 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// NEP-141 receiver (ft_transfer_call to the metapool): stake with wNEAR, liquid unstake of stNEAR
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+fn wnear_balance(wnear: &UserAccount, account_id: &str) -> u128 {
+    view_u128(wnear, "ft_balance_of", &json!({ "account_id": account_id }).to_string())
+}
+
+/// ft_transfer_call of wNEAR to the metapool with msg {"action":"stake"}
+fn stake_wnear(sim: &Simulation, wnear: &UserAccount, user: &UserAccount, amount: &str) -> ExecutionResult {
+    return call(
+        user,
+        wnear,
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": sim.metapool.account_id(),
+            "amount": amount,
+            "msg": r#"{"action":"stake"}"#,
+        })
+        .to_string(),
+        1,
+        300 * TGAS,
+    );
+}
+
+#[test]
+fn sim_wnear_stake() {
+    let sim = Simulation::new();
+    let user = sim.testnet.create_user("wnear-user".into(), ntoy(10_000));
+    let wnear = sim.deploy_wnear(&[&user]);
+
+    check_call(&sim, &call(&user, &wnear, "near_deposit", "{}", 1_000 * NEAR, 50 * TGAS));
+    // stake 500 wNEAR
+    check_call(&sim, &stake_wnear(&sim, &wnear, &user, &ntoU128(500)));
+    assert_eq!(wnear_balance(&wnear, &user.account_id()), 500 * NEAR);
+    assert_eq!(wnear_balance(&wnear, &sim.metapool.account_id()), 0);
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["st_near"]), 500 * NEAR);
+
+    // under the min deposit: ft_on_transfer fails before unwrapping, the wNEAR is refunded
+    stake_wnear(&sim, &wnear, &user, &ntoU128(1));
+    assert_eq!(wnear_balance(&wnear, &user.account_id()), 500 * NEAR);
+    assert_eq!(wnear_balance(&wnear, &sim.metapool.account_id()), 0);
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["st_near"]), 500 * NEAR);
+
+    let state = build_state(&sim);
+    bot_heartbeat(&sim, &state);
+    assert_on_chain_invariants(&sim);
+}
+
+#[test]
+fn sim_liquid_unstake_by_ft_transfer_call() {
+    let sim = Simulation::new();
+    let user = sim.testnet.create_user("stnear-user".into(), ntoy(10_000));
+    let lp = sim.testnet.create_user("lp".into(), ntoy(100_000));
+    let wnear = sim.deploy_wnear(&[&user]);
+    let mut state = stake(&sim, &user, 500 * NEAR, &build_state(&sim));
+    state = step_call(
+        &sim,
+        &lp,
+        "nslp_add_liquidity",
+        json!({}),
+        200 * TGAS,
+        10_000 * NEAR,
+        &state,
+    )
+    .unwrap()
+    .state;
+
+    // liquid unstake 100 stNEAR, received as wNEAR
+    state = step_call(
+        &sim,
+        &user,
+        "ft_transfer_call",
+        json!({
+            "receiver_id": sim.metapool.account_id(),
+            "amount": ntoU128(100),
+            "msg": json!({
+                "action": "liquid_unstake",
+                "min_expected_near": ntoU128(90),
+                "wnear": true,
+            })
+            .to_string(),
+        }),
+        300 * TGAS,
+        1,
+        &state,
+    )
+    .unwrap()
+    .state;
+    assert!(wnear_balance(&wnear, &user.account_id()) >= 90 * NEAR);
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["st_near"]), 400 * NEAR);
+
+    // liquid unstake 100 stNEAR, received as NEAR
+    let before = balance(&user);
+    state = step_call(
+        &sim,
+        &user,
+        "ft_transfer_call",
+        json!({
+            "receiver_id": sim.metapool.account_id(),
+            "amount": ntoU128(100),
+            "msg": json!({
+                "action": "liquid_unstake",
+                "min_expected_near": ntoU128(90),
+            })
+            .to_string(),
+        }),
+        300 * TGAS,
+        1,
+        &state,
+    )
+    .unwrap()
+    .state;
+    assert!(balance(&user) > before + 89 * NEAR);
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["st_near"]), 300 * NEAR);
+    // the metapool sold exactly what it received, it keeps no stNEAR
+    let info = sim.show_account_info(&sim.metapool.account_id());
+    assert_eq!(as_u128(&info["st_near"]), 0);
+
+    bot_heartbeat(&sim, &state);
+    assert_on_chain_invariants(&sim);
+}
@@ -0,0 +1,107 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// gas measurements, to size the gas constants of the multi-pool callbacks
+///
+use near_sdk::serde_json::json;
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::gas::owner_callbacks::*;
+use metapool::*;
+
+/// gas burnt by the last receipt executed by `account_id` that logged the metapool event `event`
+pub fn gas_burnt_by_receipt_with_event(res: &ExecutionResult, account_id: &str, event: &str) -> u64 {
+    let pattern = format!(r#""event":"{}""#, event);
+    let mut burnt: u64 = 0;
+    for item in res.promise_results() {
+        if let Some(pr) = item {
+            if pr.outcome().executor_id == account_id
+                && pr.outcome().logs.iter().any(|line| line.contains(&pattern))
+            {
+                burnt = pr.gas_burnt();
+            }
+        }
+    }
+    assert!(burnt > 0, "no receipt from {} logged {}", account_id, event);
+    return burnt;
+}
+
+/// stakes in all the pools and moves to the next epoch with rewards in every pool
+pub fn setup_staked_pools_with_rewards(sim: &Simulation) -> State {
+    let user = sim.testnet.create_user("staker".into(), ntoy(500_000));
+    let mut state = build_state(&sim);
+    state = step_call(
+        &sim,
+        &user,
+        "deposit_and_stake",
+        json!({}),
+        50 * TGAS,
+        100_000 * NEAR,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_distributes(&sim, &state).unwrap().state;
+
+    sim.advance_epochs(1);
+    // NEAR sent to a pool is distributed as rewards on the next ping
+    for n in 0..sim.sp.len() {
+        check_exec_result(&sim.master_account.transfer(sim.sp[n].account_id(), 100 * NEAR));
+        check_exec_result(&sim.operator.call(
+            sim.sp[n].account_id(),
+            "ping",
+            &[],
+            200 * TGAS,
+            NO_DEPOSIT,
+        ));
+    }
+    return build_state(&sim);
+}
+
+#[test]
+fn gas_distribute_rewards_batch() {
+    let sim = Simulation::new();
+    let state = setup_staked_pools_with_rewards(&sim);
+
+    let count = sim.sp.len() as u16;
+    assert!(count <= MAX_DISTRIBUTE_REWARDS_BATCH);
+    let result = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_rewards_batch",
+        json!({ "from": 0, "limit": count }),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    let res = result.res.unwrap();
+    check_exec_result(&res);
+    let queried: u16 = res.unwrap_json();
+    assert_eq!(queried, count);
+    for sp in result.state.sps.iter() {
+        assert_eq!(sp["last_asked_rewards_epoch_height"], json!(result.state.epoch.to_string()));
+    }
+
+    let burnt = gas_burnt_by_receipt_with_event(&res, &sim.metapool.account_id(), "rewards");
+    let per_pool = (burnt - std::cmp::min(burnt, ON_GET_SP_TOTAL_BALANCE_BATCH_BASE)) / count as u64;
+    println!(
+        "on_get_sp_total_balance_batch: {} pools, {} TGas burnt, {} TGas per pool",
+        count,
+        burnt / TGAS,
+        per_pool / TGAS
+    );
+    assert!(
+        burnt <= ON_GET_SP_TOTAL_BALANCE_BATCH_BASE + count as u64 * ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL,
+        "the callback burnt more than its budget"
+    );
+    assert!(
+        per_pool <= ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL,
+        "ON_GET_SP_TOTAL_BALANCE_BATCH_PER_POOL is too low, measured {}",
+        per_pool
+    );
+}
@@ -0,0 +1,58 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// staking loss in a pool: detected by distribute_rewards, socialized when there is no insurance fund
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_loss_in_a_pool() {
+    let mut sim = Simulation::new();
+    let mock_inx = sim.add_mock_staking_pool(20);
+    let user = sim.testnet.create_user("staker".into(), ntoy(500_000));
+    let mut state = stake(&sim, &user, 100_000 * NEAR, &build_state(&sim));
+    let staked = as_u128(&state.sps[mock_inx]["staked"]);
+    assert!(staked > 1_000 * NEAR);
+
+    // the pool loses 1000 NEAR of our stake
+    check_exec_result(&call(
+        &sim.owner,
+        &sim.sp[mock_inx],
+        "lose_stake",
+        &json!({ "account_id": sim.metapool.account_id(), "amount": ntoU128(1_000) }).to_string(),
+        0,
+        50 * TGAS,
+    ));
+    let metapool = &sim.metapool;
+    let price_before = as_u128(&view!(metapool.get_st_near_price()).unwrap_json_value());
+    sim.advance_epochs(1);
+    let result = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_rewards",
+        json!({ "sp_inx": mock_inx }),
+        200 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    assert!(logged_event(&result.res.unwrap(), "loss"));
+    state = result.state;
+    // no insurance coverage: the loss is socialized
+    assert_eq!(as_u128(&state.sps[mock_inx]["staked"]), staked - 1_000 * NEAR);
+    assert_eq!(result.diff.total_for_staking, -(1_000 * NEAR as i128));
+    assert_eq!(result.diff.total_actually_staked, -(1_000 * NEAR as i128));
+    let price_after = as_u128(&view!(metapool.get_st_near_price()).unwrap_json_value());
+    assert!(price_after < price_before);
+
+    bot_heartbeat(&sim, &state);
+    assert_on_chain_invariants(&sim);
+}
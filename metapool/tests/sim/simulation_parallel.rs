@@ -0,0 +1,86 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// parallel distribution (max_parallel_pools > 1): staking & unstaking more than one pool per call
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_parallel_stake_and_unstake() {
+    let sim = Simulation::new();
+    let metapool = &sim.metapool;
+    check_exec_result(&call!(sim.owner, metapool.set_max_parallel_pools(4), gas = 25 * TGAS));
+    let user = sim.testnet.create_user("parallel".into(), ntoy(500_000));
+
+    //---- parallel staking: more than one pool per call
+    let mut state = step_call(
+        &sim,
+        &user,
+        "deposit_and_stake",
+        json!({}),
+        50 * TGAS,
+        100_000 * NEAR,
+        &build_state(&sim),
+    )
+    .unwrap()
+    .state;
+    state = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_staking",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    let staked_pools = state.sps.iter().filter(|sp| as_u128(&sp["staked"]) > 0).count();
+    assert!(staked_pools > 1, "only {} pool staked", staked_pools);
+    state = bot_distributes(&sim, &state).unwrap().state;
+    assert_eq!(state.total_actually_staked, state.total_for_staking);
+    assert_on_chain_invariants(&sim);
+
+    //---- parallel unstaking: more than one pool per call
+    state = step_call(
+        &sim,
+        &user,
+        "unstake",
+        json!({ "amount": ntoU128(40_000) }),
+        100 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_end_of_epoch_clearing(&sim, &state).unwrap().state;
+    state = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_unstaking",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_distributes(&sim, &state).unwrap().state;
+    let unstaking_pools = state.sps.iter().filter(|sp| as_u128(&sp["unstaked"]) > 0).count();
+    assert!(unstaking_pools > 1, "only {} pool unstaking", unstaking_pools);
+    assert_on_chain_invariants(&sim);
+
+    // the heartbeat retrieves the unstaked funds once the waiting period ends
+    let unlock_epoch = sim.epoch() + NUM_EPOCHS_TO_UNLOCK;
+    state = run_epochs_until(&sim, &state, unlock_epoch);
+    assert_eq!(state.unstaked_in_pools, 0);
+    assert_on_chain_invariants(&sim);
+}
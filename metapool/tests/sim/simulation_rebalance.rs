@@ -0,0 +1,65 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// rebalance: unstake from the over-weight pools, at most rebalance_max_bp_per_epoch per epoch, re-staked once retrieved
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_rebalance_unstake() {
+    let sim = Simulation::new();
+    let user = sim.testnet.create_user("staker".into(), ntoy(500_000));
+    let mut state = stake(&sim, &user, 100_000 * NEAR, &build_state(&sim));
+    state = bot_heartbeat(&sim, &state);
+    assert_eq!(state.total_actually_staked, state.total_for_staking);
+
+    //---- sp3 gets more weight, sp1 is now over-weight
+    let mut pools: Vec<StakingPoolArgItem> = Vec::with_capacity(4);
+    for (n, weight_basis_points) in [1500, 2500, 2500, 3500].iter().enumerate() {
+        pools.push(StakingPoolArgItem {
+            account_id: sim.sp[n].account_id(),
+            weight_basis_points: *weight_basis_points,
+        });
+    }
+    let metapool = &sim.metapool;
+    check_exec_result(&call!(sim.owner, metapool.set_staking_pools(pools), 1, 125 * TGAS));
+    state = build_state(&sim);
+    let sp3_staked = as_u128(&state.sps[3]["staked"]);
+    let budget = state.total_actually_staked * DEFAULT_REBALANCE_MAX_BP_PER_EPOCH as u128 / 10_000;
+
+    let result = step_call(
+        &sim,
+        &sim.operator,
+        "rebalance_unstake",
+        json!({}),
+        200 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    let rebalanced: bool = result.res.unwrap().unwrap_json();
+    assert!(rebalanced);
+    state = result.state;
+    // at most rebalance_max_bp_per_epoch of the total staked
+    let unstaked = as_u128(&state.sps[1]["unstaked"]);
+    assert!(unstaked > 0);
+    assert!(unstaked <= budget);
+    assert_on_chain_invariants(&sim);
+
+    let unlock_epoch = sim.epoch() + NUM_EPOCHS_TO_UNLOCK;
+    state = run_epochs_until(&sim, &state, unlock_epoch);
+    state = bot_heartbeat(&sim, &state);
+    assert_eq!(as_u128(&state.sps[1]["unstaked"]), 0);
+    assert!(as_u128(&state.sps[3]["staked"]) > sp3_staked);
+    assert_eq!(state.total_actually_staked, state.total_for_staking);
+    assert_on_chain_invariants(&sim);
+}
+
@@ -0,0 +1,106 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// retrieve_all_matured: retrieve all the pools with the waiting period ended in one call, with a report per pool
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_retrieve_all_matured() {
+    let sim = Simulation::new();
+    let metapool = &sim.metapool;
+    check_exec_result(&call!(sim.owner, metapool.set_max_parallel_pools(4), gas = 25 * TGAS));
+    let user = sim.testnet.create_user("parallel".into(), ntoy(500_000));
+
+    let mut state = step_call(
+        &sim,
+        &user,
+        "deposit_and_stake",
+        json!({}),
+        50 * TGAS,
+        100_000 * NEAR,
+        &build_state(&sim),
+    )
+    .unwrap()
+    .state;
+    state = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_staking",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_distributes(&sim, &state).unwrap().state;
+
+    // unstake from several pools
+    state = step_call(
+        &sim,
+        &user,
+        "unstake",
+        json!({ "amount": ntoU128(40_000) }),
+        100 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_end_of_epoch_clearing(&sim, &state).unwrap().state;
+    state = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_unstaking",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_distributes(&sim, &state).unwrap().state;
+    let unstaking_pools = state.sps.iter().filter(|sp| as_u128(&sp["unstaked"]) > 0).count();
+    assert!(unstaking_pools > 1, "only {} pool unstaking", unstaking_pools);
+    let unstaked_in_pools = state.unstaked_in_pools;
+    assert_on_chain_invariants(&sim);
+
+    // retrieve all the pools in one call
+    sim.advance_epochs(NUM_EPOCHS_TO_UNLOCK);
+    let result = step_call(
+        &sim,
+        &sim.operator,
+        "retrieve_all_matured",
+        json!({}),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    let report = result.res.unwrap().unwrap_json_value();
+    state = result.state;
+    let report = report.as_array().unwrap();
+    assert_eq!(report.len(), unstaking_pools);
+    let mut retrieved: u128 = 0;
+    for item in report {
+        assert_eq!(item["failed"], json!(false), "{}", item);
+        retrieved += as_u128(&item["retrieved"]);
+    }
+    assert_eq!(retrieved, unstaked_in_pools);
+    assert_eq!(state.unstaked_in_pools, 0);
+    assert_eq!(state.total_unstaked_and_waiting, 0);
+    for sp in state.sps.iter() {
+        assert_eq!(sp["busy_lock"], json!(false));
+        assert_eq!(sp["needs_resync"], json!(false));
+    }
+    assert_on_chain_invariants(&sim);
+}
@@ -0,0 +1,47 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// distribute_rewards_batch: rewards of all the pools in one call
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_distribute_rewards_batch() {
+    let sim = Simulation::new();
+    let metapool = &sim.metapool;
+    let user = sim.testnet.create_user("staker".into(), ntoy(500_000));
+    let mut state = stake(&sim, &user, 100_000 * NEAR, &build_state(&sim));
+    state = bot_heartbeat(&sim, &state);
+
+    // rewards of all the pools in one call
+    sim.advance_epochs(1);
+    for n in 0..sim.sp.len() {
+        check_exec_result(&sim.master_account.transfer(sim.sp[n].account_id(), 100 * NEAR));
+        check_exec_result(&sim.operator.call(sim.sp[n].account_id(), "ping", &[], 200 * TGAS, NO_DEPOSIT));
+    }
+    let price_before = as_u128(&view!(metapool.get_st_near_price()).unwrap_json_value());
+    let result = step_call(
+        &sim,
+        &sim.operator,
+        "distribute_rewards_batch",
+        json!({ "from": 0, "limit": sim.sp.len() }),
+        300 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    state = result.state;
+    for sp in state.sps.iter() {
+        assert_eq!(sp["last_asked_rewards_epoch_height"], json!(state.epoch.to_string()));
+    }
+    assert!(as_u128(&view!(metapool.get_st_near_price()).unwrap_json_value()) > price_before);
+    assert_on_chain_invariants(&sim);
+}
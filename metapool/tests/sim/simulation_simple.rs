@@ -149,7 +149,7 @@ fn simtest_simple() {
   {
     let res = call!(
       carol,
-      metapool.nslp_add_liquidity(),
+      metapool.nslp_add_liquidity(None),
       carol_deposit,
       50 * TGAS
     );
@@ -163,22 +163,22 @@ fn simtest_simple() {
   println!("----------------------------------");
   println!("------- small qty add-remove liq --");
   {
-    let r1 = call!(bob, metapool.nslp_add_liquidity(), 30 * NEAR, 50 * TGAS);
+    let r1 = call!(bob, metapool.nslp_add_liquidity(None), 30 * NEAR, 50 * TGAS);
     check_exec_result(&r1);
     let bob_info_1 = sim.show_account_info(&bob.account_id());
     assert!(as_u128(&bob_info_1["nslp_shares"]) == 30 * NEAR);
     let r2 = call!(
       bob,
-      metapool.nslp_remove_liquidity(U128::from(30 * NEAR + 9)),
+      metapool.nslp_remove_liquidity(U128::from(30 * NEAR + 9), None, None),
       gas = 100 * TGAS
     );
     check_exec_result(&r2);
     let bob_info_2 = sim.show_account_info(&bob.account_id());
     assert!(as_u128(&bob_info_2["nslp_shares"]) == 0);
-    call!(bob, metapool.nslp_add_liquidity(), 30 * NEAR, 50 * TGAS);
+    call!(bob, metapool.nslp_add_liquidity(None), 30 * NEAR, 50 * TGAS);
     let r4 = call!(
       bob,
-      metapool.nslp_remove_liquidity(U128::from(30 * NEAR + 1 - ONE_MILLI_NEAR)),
+      metapool.nslp_remove_liquidity(U128::from(30 * NEAR + 1 - ONE_MILLI_NEAR), None, None),
       gas = 100 * TGAS
     );
     check_exec_result(&r4);
@@ -231,7 +231,7 @@ fn simtest_simple() {
   {
     let ads_res = call!(
       alice,
-      metapool.unstake(alice_unstaking.into()),
+      metapool.unstake(alice_unstaking.into(), None),
       gas = 50 * TGAS
     );
     check_exec_result(&ads_res);
@@ -374,7 +374,7 @@ fn simtest_simple() {
     println!("-- nslp_remove_liquidity");
     let res = call!(
       carol,
-      metapool.nslp_remove_liquidity(U128::from(AMOUNT)),
+      metapool.nslp_remove_liquidity(U128::from(AMOUNT), None, None),
       gas = 100 * TGAS
     );
     check_exec_result(&res);
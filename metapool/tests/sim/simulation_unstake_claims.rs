@@ -0,0 +1,87 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// unstake claims: several delayed-unstake claims per account, each one with its own unlock epoch
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_unstake_claims_queue() {
+    let sim = Simulation::new();
+    let user = sim.testnet.create_user("claimer".into(), ntoy(500_000));
+    let mut state = stake(&sim, &user, 100_000 * NEAR, &build_state(&sim));
+
+    // two unstakes in different epochs: two claims, each one with its own unlock epoch
+    state = step_call(
+        &sim,
+        &user,
+        "unstake",
+        json!({ "amount": ntoU128(1_000) }),
+        100 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    state = bot_heartbeat(&sim, &state);
+    sim.advance_epochs(1);
+    state = step_call(
+        &sim,
+        &user,
+        "unstake",
+        json!({ "amount": ntoU128(2_000) }),
+        100 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    let unlock = unlock_epochs(&sim, &user.account_id());
+    assert_eq!(unlock.len(), 2);
+    assert!(unlock[0] < unlock[1], "the 2nd unstake must not delay the 1st claim");
+
+    // the 1st claim matures alone
+    state = run_epochs_until(&sim, &state, unlock[0]);
+    let before = balance(&user);
+    state = step_call(
+        &sim,
+        &user,
+        "withdraw_unstaked",
+        json!({}),
+        50 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    assert!(balance(&user) > before + 999 * NEAR);
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["unstaked"]), 2_000 * NEAR);
+    assert_eq!(unlock_epochs(&sim, &user.account_id()), vec![unlock[1]]);
+
+    // then the 2nd
+    state = run_epochs_until(&sim, &state, unlock[1]);
+    step_call(
+        &sim,
+        &user,
+        "withdraw_unstaked",
+        json!({}),
+        50 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap();
+    let info = sim.show_account_info(&user.account_id());
+    assert_eq!(as_u128(&info["unstaked"]), 0);
+    assert!(unlock_epochs(&sim, &user.account_id()).is_empty());
+
+    assert_on_chain_invariants(&sim);
+}
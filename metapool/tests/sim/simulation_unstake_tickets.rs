@@ -0,0 +1,111 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+///
+/// unstake tickets: a delayed-unstake claim minted as a NEP-171 token, transferred and redeemed by its holder
+/// The sim invariants are checked after each step (step_call) and the on-chain check_invariants at the end
+///
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{call, view, ExecutionResult, UserAccount};
+
+use crate::sim_contract_state::*;
+use crate::sim_setup::*;
+use crate::sim_steps::*;
+use crate::sim_utils::*;
+use metapool::*;
+
+#[test]
+fn sim_unstake_tickets() {
+    let sim = Simulation::new();
+    let metapool = &sim.metapool;
+    let seller = sim.testnet.create_user("seller".into(), ntoy(500_000));
+    let buyer = sim.testnet.create_user("buyer".into(), ntoy(1_000));
+    let mut state = stake(&sim, &seller, 100_000 * NEAR, &build_state(&sim));
+
+    // minting requires the storage deposit
+    assert!(step_call(
+        &sim,
+        &seller,
+        "unstake",
+        json!({ "amount": ntoU128(1_000), "mint_ticket": true }),
+        100 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .is_err());
+    state = step_call(
+        &sim,
+        &seller,
+        "unstake",
+        json!({ "amount": ntoU128(1_000), "mint_ticket": true }),
+        100 * TGAS,
+        UNSTAKE_TICKET_STORAGE_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    assert_eq!(state.unstake_tickets_storage, UNSTAKE_TICKET_STORAGE_DEPOSIT);
+    // the claim is in the ticket, not in the account
+    let info = sim.show_account_info(&seller.account_id());
+    assert_eq!(as_u128(&info["unstaked"]), 0);
+
+    let tokens = view!(metapool.nft_tokens_for_owner(seller.valid_account_id(), None, None))
+        .unwrap_json_value();
+    let token_id = tokens[0]["token_id"].as_str().unwrap().to_string();
+    let ticket = view!(metapool.get_unstake_ticket(token_id.clone())).unwrap_json_value();
+    assert_eq!(as_u128(&ticket["amount"]), 1_000 * NEAR);
+    let unlock_epoch = as_u128(&ticket["unlock_epoch"]) as u64;
+
+    // sell it
+    state = step_call(
+        &sim,
+        &seller,
+        "nft_transfer",
+        json!({ "receiver_id": buyer.account_id(), "token_id": token_id }),
+        50 * TGAS,
+        1,
+        &state,
+    )
+    .unwrap()
+    .state;
+    // not redeemable before maturity
+    assert!(step_call(
+        &sim,
+        &buyer,
+        "redeem_unstake_ticket",
+        json!({ "token_id": token_id }),
+        50 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .is_err());
+
+    state = run_epochs_until(&sim, &state, unlock_epoch);
+    // only the holder can redeem
+    assert!(step_call(
+        &sim,
+        &seller,
+        "redeem_unstake_ticket",
+        json!({ "token_id": token_id }),
+        50 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .is_err());
+    let before = balance(&buyer);
+    state = step_call(
+        &sim,
+        &buyer,
+        "redeem_unstake_ticket",
+        json!({ "token_id": token_id }),
+        50 * TGAS,
+        NO_DEPOSIT,
+        &state,
+    )
+    .unwrap()
+    .state;
+    assert!(balance(&buyer) > before + 999 * NEAR);
+    assert_eq!(state.unstake_tickets_storage, 0);
+    assert_eq!(as_u128(&view!(metapool.nft_total_supply()).unwrap_json_value()), 0);
+
+    assert_on_chain_invariants(&sim);
+}
@@ -0,0 +1,152 @@
+//------------------------------------
+//------------------------------------
+//---- TEST ONLY CONTRACT ------------
+//------------------------------------
+//------------------------------------
+// staking-pool mock for the sim tests. Same interface as core-contracts/staking-pool
+// but balances are plain amounts (no shares, no epochs): unstaked can be withdrawn at once.
+// The owner can make a delegator lose stake (lose_stake), to test the loss path of the meta-pool
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Promise};
+
+#[global_allocator]
+static ALLOC: near_sdk::wee_alloc::WeeAlloc = near_sdk::wee_alloc::WeeAlloc::INIT;
+
+type U128String = U128;
+
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct Delegator {
+    pub staked: u128,
+    pub unstaked: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardFeeFraction {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HumanReadableAccount {
+    pub account_id: AccountId,
+    pub unstaked_balance: U128String,
+    pub staked_balance: U128String,
+    pub can_withdraw: bool,
+}
+
+//contract state
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct MockStakingPool {
+    pub owner_id: AccountId,
+    pub reward_fee_fraction: RewardFeeFraction,
+    pub accounts: LookupMap<AccountId, Delegator>,
+}
+
+#[near_bindgen]
+impl MockStakingPool {
+    #[init]
+    pub fn new(owner_id: AccountId, reward_fee_fraction: RewardFeeFraction) -> Self {
+        assert!(!env::state_exists(), "This contract is already initialized");
+        return Self {
+            owner_id,
+            reward_fee_fraction,
+            accounts: LookupMap::new(b"a".to_vec()),
+        };
+    }
+
+    fn internal_get(&self, account_id: &AccountId) -> Delegator {
+        self.accounts.get(account_id).unwrap_or_default()
+    }
+
+    pub fn ping(&mut self) {}
+
+    #[payable]
+    pub fn deposit(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get(&account_id);
+        acc.unstaked += env::attached_deposit();
+        self.accounts.insert(&account_id, &acc);
+    }
+
+    #[payable]
+    pub fn deposit_and_stake(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get(&account_id);
+        acc.staked += env::attached_deposit();
+        self.accounts.insert(&account_id, &acc);
+    }
+
+    pub fn stake(&mut self, amount: U128String) {
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get(&account_id);
+        assert!(acc.unstaked >= amount.0, "Not enough unstaked balance to stake");
+        acc.unstaked -= amount.0;
+        acc.staked += amount.0;
+        self.accounts.insert(&account_id, &acc);
+    }
+
+    pub fn unstake(&mut self, amount: U128String) {
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get(&account_id);
+        assert!(acc.staked >= amount.0, "Not enough staked balance to unstake");
+        acc.staked -= amount.0;
+        acc.unstaked += amount.0;
+        self.accounts.insert(&account_id, &acc);
+    }
+
+    pub fn withdraw(&mut self, amount: U128String) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let mut acc = self.internal_get(&account_id);
+        assert!(acc.unstaked >= amount.0, "Not enough unstaked balance to withdraw");
+        acc.unstaked -= amount.0;
+        self.accounts.insert(&account_id, &acc);
+        return Promise::new(account_id).transfer(amount.0);
+    }
+
+    pub fn withdraw_all(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let unstaked = self.internal_get(&account_id).unstaked;
+        return self.withdraw(unstaked.into());
+    }
+
+    /// test method: account_id loses `amount` of its stake, as after a slashing
+    pub fn lose_stake(&mut self, account_id: AccountId, amount: U128String) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "only the owner");
+        let mut acc = self.internal_get(&account_id);
+        acc.staked = acc.staked.saturating_sub(amount.0);
+        self.accounts.insert(&account_id, &acc);
+    }
+
+    pub fn get_account_staked_balance(&self, account_id: AccountId) -> U128String {
+        self.internal_get(&account_id).staked.into()
+    }
+
+    pub fn get_account_unstaked_balance(&self, account_id: AccountId) -> U128String {
+        self.internal_get(&account_id).unstaked.into()
+    }
+
+    pub fn get_account_total_balance(&self, account_id: AccountId) -> U128String {
+        let acc = self.internal_get(&account_id);
+        (acc.staked + acc.unstaked).into()
+    }
+
+    pub fn get_account(&self, account_id: AccountId) -> HumanReadableAccount {
+        let acc = self.internal_get(&account_id);
+        return HumanReadableAccount {
+            account_id,
+            unstaked_balance: acc.unstaked.into(),
+            staked_balance: acc.staked.into(),
+            can_withdraw: true,
+        };
+    }
+
+    pub fn get_reward_fee_fraction(&self) -> RewardFeeFraction {
+        self.reward_fee_fraction.clone()
+    }
+}
@@ -0,0 +1,53 @@
+//------------------------------------
+//------------------------------------
+//---- TEST ONLY CONTRACT ------------
+//------------------------------------
+//------------------------------------
+// wNEAR mock for the sim tests. Same interface as near/core-contracts w-near:
+// NEP-141 + storage management, near_deposit wraps the attached NEAR, near_withdraw unwraps
+use near_contract_standards::fungible_token::FungibleToken;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, env, log, near_bindgen, PanicOnDefault, Promise};
+
+near_sdk::setup_alloc!();
+
+//contract state
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct MockWrappedNear {
+    pub token: FungibleToken,
+}
+
+near_contract_standards::impl_fungible_token_core!(MockWrappedNear, token);
+near_contract_standards::impl_fungible_token_storage!(MockWrappedNear, token);
+
+#[near_bindgen]
+impl MockWrappedNear {
+    #[init]
+    pub fn new() -> Self {
+        assert!(!env::state_exists(), "This contract is already initialized");
+        return Self {
+            token: FungibleToken::new(b"a".to_vec()),
+        };
+    }
+
+    /// wraps the attached NEAR, the caller must be registered (storage_deposit)
+    #[payable]
+    pub fn near_deposit(&mut self) {
+        let amount = env::attached_deposit();
+        let account_id = env::predecessor_account_id();
+        self.token.internal_deposit(&account_id, amount);
+        log!("Deposit {} NEAR to {}", amount, account_id);
+    }
+
+    /// unwraps amount, the NEAR is sent to the caller
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.token.internal_withdraw(&account_id, amount.0);
+        log!("Withdraw {} NEAR from {}", amount.0, account_id);
+        return Promise::new(account_id).transfer(amount.0);
+    }
+}